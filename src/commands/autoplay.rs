@@ -0,0 +1,27 @@
+use serenity::{
+    async_trait,
+    model::prelude::interaction::application_command::ApplicationCommandInteraction,
+    prelude::Context,
+};
+
+use super::{guild_id, play::autoplay, registry::Command, send_msg};
+use crate::{errors::Result, locale::Lang};
+
+pub struct Autoplay;
+
+#[async_trait]
+impl Command for Autoplay {
+    fn name(&self) -> &'static str {
+        "autoplay"
+    }
+
+    async fn run(&self, interaction: &ApplicationCommandInteraction, ctx: &Context) -> Result<()> {
+        let lang = Lang::of(interaction);
+        let guild_id = guild_id(interaction)?;
+        if autoplay::toggle(guild_id).await? {
+            send_msg(ctx, interaction, &lang.tr("autoplay.enabled")).await
+        } else {
+            send_msg(ctx, interaction, &lang.tr("autoplay.disabled")).await
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use serenity::{
+    model::prelude::{interaction::application_command::ApplicationCommandInteraction, GuildId, RoleId},
+    prelude::Context,
+};
+use songbird::Call;
+use tokio::sync::OwnedMutexGuard;
+
+use super::{guild_id, manager, user_channel};
+use crate::{
+    errors::{Error, Result},
+    locale::Lang,
+    settings,
+};
+
+const DJ_ROLE: &str = "dj";
+
+/// Preconditions of commands controlling playback. The bot always has to be connected.
+#[derive(Clone, Copy, Default)]
+pub struct Guard {
+    same_channel: bool,
+    dj: bool,
+}
+
+/// What a command gets once its guard lets it through.
+pub struct Voice {
+    pub guild_id: GuildId,
+    /// Language to reply to the caller in.
+    pub lang: Lang,
+    pub handler: OwnedMutexGuard<Call>,
+}
+
+impl Guard {
+    pub const fn new() -> Self {
+        Guard {
+            same_channel: false,
+            dj: false,
+        }
+    }
+
+    /// User has to sit in the channel the bot plays in.
+    pub const fn same_channel(self) -> Self {
+        Guard {
+            same_channel: true,
+            ..self
+        }
+    }
+
+    /// User has to have the DJ role set in settings or named "DJ", if the server has one.
+    pub const fn dj(self) -> Self {
+        Guard { dj: true, ..self }
+    }
+
+    pub async fn check(self, ctx: &Context, interaction: &ApplicationCommandInteraction) -> Result<Voice> {
+        let guild_id = guild_id(interaction)?;
+        if self.dj && !is_dj(ctx, interaction, guild_id) {
+            return Err(Error::NotDj);
+        }
+
+        let handler_lock = manager(ctx).await?.get(guild_id).ok_or(Error::BotNotInVoice)?;
+        let handler = handler_lock.lock_owned().await;
+        let bot_channel = handler.current_channel().ok_or(Error::BotNotInVoice)?;
+
+        if self.same_channel && user_channel(ctx, interaction)?.0 != bot_channel.0 {
+            return Err(Error::OtherChannel);
+        }
+
+        Ok(Voice {
+            guild_id,
+            lang: Lang::of(interaction),
+            handler,
+        })
+    }
+}
+
+fn dj_role(ctx: &Context, guild_id: GuildId) -> Option<RoleId> {
+    if let Some(role) = settings::get(guild_id).dj_role {
+        return Some(role);
+    }
+    let guild = ctx.cache.guild(guild_id)?;
+    guild
+        .roles
+        .values()
+        .find(|role| role.name.to_lowercase() == DJ_ROLE)
+        .map(|role| role.id)
+}
+
+/// Admins are always DJs, and so is everyone on servers without the role.
+fn is_dj(ctx: &Context, interaction: &ApplicationCommandInteraction, guild_id: GuildId) -> bool {
+    let member = match &interaction.member {
+        Some(member) => member,
+        None => return false,
+    };
+    if member.permissions.map_or(false, |p| p.administrator()) {
+        return true;
+    }
+    match dj_role(ctx, guild_id) {
+        Some(role) => member.roles.contains(&role),
+        None => true,
+    }
+}
@@ -0,0 +1,40 @@
+use serenity::{
+    async_trait,
+    model::prelude::interaction::application_command::ApplicationCommandInteraction,
+    prelude::Context,
+};
+
+use super::{
+    guard::{Guard, Voice},
+    play::history,
+    registry::Command,
+    send_msg,
+};
+use crate::errors::Result;
+
+pub struct Like;
+
+#[async_trait]
+impl Command for Like {
+    fn name(&self) -> &'static str {
+        "like"
+    }
+
+    async fn run(&self, interaction: &ApplicationCommandInteraction, ctx: &Context) -> Result<()> {
+        let Voice { guild_id, handler, lang, .. } = Guard::new().check(ctx, interaction).await?;
+        let url = match handler
+            .queue()
+            .current()
+            .and_then(|track| track.metadata().source_url.clone())
+        {
+            Some(url) => url,
+            None => return send_msg(ctx, interaction, &lang.tr("like.nothing")).await,
+        };
+
+        if history::like(guild_id, interaction.user.id, url).await {
+            send_msg(ctx, interaction, &lang.tr("like.liked")).await
+        } else {
+            send_msg(ctx, interaction, &lang.tr("like.already")).await
+        }
+    }
+}
@@ -1,34 +1,92 @@
-use log::warn;
+use std::{sync::Arc, time::Duration};
+
 use serenity::{
     model::prelude::{interaction::{
         application_command::ApplicationCommandInteraction, InteractionResponseType,
-    }, Message},
+    }, ChannelId, GuildId, Message},
     prelude::Context,
 };
+use songbird::Songbird;
 
+pub mod autoplay;
+mod guard;
+pub mod like;
 pub mod pause;
 pub mod ping;
 pub mod play;
+pub mod podcast;
+pub mod radio;
+pub mod registration;
+mod registry;
 pub mod repeat;
 pub mod resume;
+pub mod search;
+pub mod settings;
 pub mod skip;
 pub mod stop;
 
-async fn send_msg(ctx: &Context, interaction: &ApplicationCommandInteraction, content: &str) {
-    if let Err(why) = interaction
+use crate::errors::{Error, Result};
+pub use registry::COMMANDS;
+
+async fn send_msg(ctx: &Context, interaction: &ApplicationCommandInteraction, content: &str) -> Result<()> {
+    interaction
         .create_interaction_response(&ctx.http, |response| {
             response
                 .kind(InteractionResponseType::ChannelMessageWithSource)
                 .interaction_response_data(|message| message.content(content))
         })
+        .await?;
+    Ok(())
+}
+
+async fn edit_msg(ctx: &Context, interaction: &ApplicationCommandInteraction, content: &str) -> Result<Message> {
+    Ok(interaction
+        .edit_original_interaction_response(&ctx.http, |response| response.content(content))
+        .await?)
+}
+
+fn guild_id(interaction: &ApplicationCommandInteraction) -> Result<GuildId> {
+    interaction.guild_id.ok_or(Error::NotInGuild)
+}
+
+/// Voice channel the user who ran the command sits in.
+fn user_channel(ctx: &Context, interaction: &ApplicationCommandInteraction) -> Result<ChannelId> {
+    let guild = ctx.cache.guild(guild_id(interaction)?).ok_or(Error::NotInGuild)?;
+    guild
+        .voice_states
+        .get(&interaction.user.id)
+        .and_then(|voice_state| voice_state.channel_id)
+        .ok_or(Error::NotInVoice)
+}
+
+async fn manager(ctx: &Context) -> Result<Arc<Songbird>> {
+    songbird::get(ctx)
         .await
-    {
-        warn!("Cannot respond to slash command: {why}");
-    }
+        .ok_or_else(|| Error::Config("Songbird is not registered".to_string()))
 }
 
-async fn edit_msg(ctx: &Context, interaction: &ApplicationCommandInteraction, content: &str) -> Message {
+/// Option value of string option `name`.
+fn string_option(interaction: &ApplicationCommandInteraction, name: &str) -> String {
     interaction
-        .edit_original_interaction_response(&ctx.http, |response| response.content(content))
-        .await.unwrap()
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_ref())
+        .and_then(|value| value.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        text.chars().take(max - 1).collect::<String>() + "…"
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
 }
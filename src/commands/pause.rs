@@ -1,44 +1,30 @@
 use serenity::{
-    builder::CreateApplicationCommand,
+    async_trait,
     model::prelude::interaction::application_command::ApplicationCommandInteraction,
     prelude::Context,
 };
 
-use super::send_msg;
+use super::{
+    guard::{Guard, Voice},
+    registry::Command,
+    send_msg,
+};
+use crate::errors::Result;
+
+pub struct Pause;
+
+#[async_trait]
+impl Command for Pause {
+    fn name(&self) -> &'static str {
+        "pause"
+    }
 
-pub async fn run(interaction: &ApplicationCommandInteraction, ctx: &Context) {
-    let guild_id = interaction.guild_id.unwrap();
-    let guild = ctx.cache.guild(guild_id).unwrap();
-    let menager = songbird::get(ctx).await.unwrap().clone();
-    let handler_lock = match menager.get(guild_id) {
-        Some(handler) => handler,
-        None => return send_msg(ctx, interaction, "Bot is not connected to voice channel").await,
-    };
-    let handler = handler_lock.lock().await;
-    let voice_channel = match guild
-        .voice_states
-        .get(&interaction.user.id)
-        .and_then(|voice_state| voice_state.channel_id) 
-    {
-        Some(v) => v.0,                                                                              
-        None => return send_msg(ctx, interaction, "You are not connected to voice channel").await
-    };
-    let bot_voice_channel = match handler.current_channel() {
-        Some(v) => v.0,
-        None => return send_msg(ctx, interaction, "Bot is not connected to voice channel").await
-        
-    };
-    if voice_channel == bot_voice_channel {
+    async fn run(&self, interaction: &ApplicationCommandInteraction, ctx: &Context) -> Result<()> {
+        let Voice { handler, lang, .. } = Guard::new().same_channel().dj().check(ctx, interaction).await?;
         if handler.queue().pause().is_ok() {
-            send_msg(ctx, interaction, "Song paused").await
+            send_msg(ctx, interaction, &lang.tr("pause.done")).await
         } else {
-            send_msg(ctx, interaction, "Cannot pause song").await
+            send_msg(ctx, interaction, &lang.tr("pause.failed")).await
         }
-    } else {
-        send_msg(ctx, interaction, "You are not connected to voice channel with bot").await
     }
 }
-
-pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
-    command.name("pause").description("pause current song")
-}
@@ -1,16 +1,22 @@
 use serenity::{
-    builder::CreateApplicationCommand,
+    async_trait,
     model::prelude::interaction::application_command::ApplicationCommandInteraction,
     prelude::Context,
 };
 
-use super::send_msg;
+use super::{registry::Command, send_msg};
+use crate::{errors::Result, locale::Lang};
 
-pub async fn run(interaction: &ApplicationCommandInteraction, ctx: &Context) {
-    send_msg(ctx, interaction, "I'm alive :)").await;
+pub struct Ping;
 
-}
+#[async_trait]
+impl Command for Ping {
+    fn name(&self) -> &'static str {
+        "ping"
+    }
 
-pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
-    command.name("ping").description("A ping command")
+    async fn run(&self, interaction: &ApplicationCommandInteraction, ctx: &Context) -> Result<()> {
+        let lang = Lang::of(interaction);
+        send_msg(ctx, interaction, &lang.tr("ping.alive")).await
+    }
 }
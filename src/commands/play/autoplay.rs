@@ -0,0 +1,188 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, LazyLock, Mutex, Weak},
+    time::Duration,
+};
+
+use log::{info, warn};
+use reqwest::Url;
+use serenity::{
+    async_trait,
+    http::Http,
+    model::prelude::{GuildId, UserId},
+};
+use songbird::{create_player, input::Metadata, Call, Event, EventContext, EventHandler, Songbird, TrackEvent};
+
+use super::{events::SongStart, history, recovery::StreamRecovery, resolver::REGISTRY, youtube, Queued};
+use crate::settings;
+
+/// Tracks ending further than this from their duration were stopped or skipped, not finished.
+const END_TOLERANCE: Duration = Duration::from_secs(5);
+
+/// Calls already watched, songbird creates a new one after the old one is removed.
+static WATCHED: LazyLock<Mutex<HashMap<GuildId, Weak<tokio::sync::Mutex<Call>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Flips autoplay of given guild, returning whether it is now enabled. Kept in settings, so it survives restarts.
+pub async fn toggle(guild_id: GuildId) -> rusqlite::Result<bool> {
+    let mut settings = settings::get(guild_id);
+    settings.autoplay = !settings.autoplay;
+    let enabled = settings.autoplay;
+    settings::set(guild_id, settings).await?;
+    Ok(enabled)
+}
+
+/// Starts listening for the queue running out, once per call. `handler` is the locked `handler_lock`.
+pub fn watch(
+    handler_lock: &Arc<tokio::sync::Mutex<Call>>,
+    handler: &mut Call,
+    guild_id: GuildId,
+    http: Arc<Http>,
+    manager: Arc<Songbird>,
+    bot: UserId,
+) {
+    let mut watched = WATCHED.lock().unwrap();
+    if watched
+        .get(&guild_id)
+        .map_or(false, |call| Weak::ptr_eq(call, &Arc::downgrade(handler_lock)))
+    {
+        return;
+    }
+    watched.insert(guild_id, Arc::downgrade(handler_lock));
+    handler.add_global_event(
+        Event::Track(TrackEvent::End),
+        QueueEnd {
+            guild_id,
+            http,
+            manager,
+            bot,
+        },
+    );
+}
+
+fn video_id(uri: &str) -> Option<String> {
+    let url = Url::parse(uri).ok()?;
+    match url.host_str()? {
+        "youtu.be" => url.path_segments()?.next().map(str::to_string),
+        _ => url
+            .query_pairs()
+            .find(|(key, _)| key == "v")
+            .map(|(_, id)| id.to_string()),
+    }
+}
+
+/// Picks track from yt mix of the last played one, leaving out anything played recently.
+async fn related(guild_id: GuildId, last: &Metadata) -> Option<String> {
+    let id = match last.source_url.as_deref().and_then(video_id) {
+        Some(id) => id,
+        None => {
+            let query = format!(
+                "{} {}",
+                last.title.as_deref().unwrap_or_default(),
+                last.artist.as_deref().unwrap_or_default()
+            );
+            let found = youtube::search(&query, 1).await;
+            video_id(&found.first()?.url)?
+        }
+    };
+
+    let recent: HashSet<String> = history::recent(guild_id)
+        .await
+        .iter()
+        .filter_map(|played| video_id(&played.url))
+        .chain([id.clone()])
+        .collect();
+    let mix = youtube::playlist(&format!("https://www.youtube.com/watch?v={id}&list=RD{id}")).await?;
+    mix.into_iter()
+        .find(|url| video_id(url).map_or(false, |id| !recent.contains(&id)))
+}
+
+struct QueueEnd {
+    guild_id: GuildId,
+    http: Arc<Http>,
+    manager: Arc<Songbird>,
+    bot: UserId,
+}
+
+#[async_trait]
+impl EventHandler for QueueEnd {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let (state, track) = match ctx {
+            EventContext::Track(tracks) => tracks.last()?,
+            _ => return None,
+        };
+        let settings = settings::get(self.guild_id);
+        if !settings.autoplay {
+            return None;
+        }
+        let previous = Queued::of(track).await?;
+        // Recovered streams start mid-track
+        let finished = track
+            .metadata()
+            .duration
+            .map_or(false, |duration| previous.offset + state.position + END_TOLERANCE >= duration);
+        if !finished {
+            return None;
+        }
+
+        let handler_lock = self.manager.get(self.guild_id)?;
+        let empty = handler_lock
+            .lock()
+            .await
+            .queue()
+            .current_queue()
+            .iter()
+            .all(|queued| queued.uuid() == track.uuid());
+        if !empty {
+            return None;
+        }
+
+        let url = match related(self.guild_id, track.metadata()).await {
+            Some(url) => url,
+            None => {
+                warn!("Autoplay found nothing related to {:?}", track.metadata().title);
+                return None;
+            }
+        };
+        let source = match REGISTRY.resolve_track(&url).await {
+            Ok(source) => source,
+            Err(e) => {
+                warn!("Autoplay cannot play {url}: {e:?}");
+                return None;
+            }
+        };
+
+        // Announced like the track before, unless the guild moved announcements since
+        let channel_id = settings.announce_channel.unwrap_or(previous.announce);
+
+        let (mut next, next_handle) = create_player(source.into());
+        next.set_volume(settings.volume());
+        if let Err(e) = next_handle.add_event(
+            Event::Periodic(Duration::from_secs(0), None),
+            SongStart::autoplay(channel_id, self.http.clone(), previous.lang),
+        ) {
+            warn!("{e}");
+        }
+        if let Err(e) = next_handle.add_event(
+            Event::Track(TrackEvent::End),
+            StreamRecovery::new(self.guild_id, channel_id, self.http.clone(), self.manager.clone()),
+        ) {
+            warn!("{e}");
+        }
+        Queued {
+            requester: None,
+            announce: channel_id,
+            offset: Duration::ZERO,
+            lang: previous.lang,
+        }
+        .attach(&next_handle)
+        .await;
+        let metadata = next_handle.metadata();
+        if let (Some(title), Some(url)) = (metadata.title.clone(), metadata.source_url.clone()) {
+            info!("Autoplay picked {title}");
+            history::record(self.guild_id, history::Played { title, url, user: self.bot });
+        }
+        handler_lock.lock().await.enqueue(next);
+        None
+    }
+}
@@ -0,0 +1,163 @@
+use std::time::Duration;
+
+use log::warn;
+use reqwest::Url;
+use serde_json::Value;
+use serenity::async_trait;
+use songbird::input::{
+    error::{Error, Result},
+    restartable::Restart,
+    Codec, Container, Input, Metadata, Restartable,
+};
+
+use super::{
+    resolver::{self, Resolved, Resolver, Skip},
+    stream, CLIENT,
+};
+
+
+fn unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Every album and track page embeds its player data as html escaped json in `data-tralbum`.
+async fn tralbum(uri: &str) -> Option<Value> {
+    let page = CLIENT.get(uri).send().await.ok()?.text().await.ok()?;
+    let data = page.split("data-tralbum=\"").nth(1)?.split('"').next()?;
+    match serde_json::from_str(&unescape(data)) {
+        Ok(json) => Some(json),
+        Err(e) => {
+            warn!("Cannot parse Bandcamp data of {uri}: {e}");
+            None
+        }
+    }
+}
+
+fn metadata(tralbum: &Value, track: &Value, uri: &str) -> Metadata {
+    let album = tralbum.get("current").and_then(|c| c.get("title")).and_then(Value::as_str);
+    Metadata {
+        track: track
+            .get("track_num")
+            .and_then(Value::as_u64)
+            .map(|num| num.to_string()),
+        artist: tralbum.get("artist").and_then(Value::as_str).map(str::to_string),
+        date: tralbum
+            .get("album_release_date")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        channels: Some(2),
+        channel: album.map(str::to_string),
+        start_time: None,
+        duration: track
+            .get("duration")
+            .and_then(Value::as_f64)
+            .map(Duration::from_secs_f64),
+        sample_rate: None,
+        source_url: Some(uri.to_string()),
+        title: track.get("title").and_then(Value::as_str).map(str::to_string),
+        thumbnail: tralbum
+            .get("art_id")
+            .and_then(Value::as_u64)
+            .map(|id| format!("https://f4.bcbits.com/img/a{id}_10.jpg")),
+    }
+}
+
+fn stream_url(track: &Value) -> Option<&str> {
+    track.get("file").and_then(|f| f.get("mp3-128")).and_then(Value::as_str)
+}
+
+struct BandcampRestarter {
+    uri: String,
+    metadata: Metadata,
+}
+
+#[async_trait]
+impl Restart for BandcampRestarter {
+    async fn lazy_init(&mut self) -> Result<(Option<Metadata>, Codec, Container)> {
+        Ok((Some(self.metadata.clone()), Codec::FloatPcm, Container::Raw))
+    }
+
+    async fn call_restart(&mut self, time: Option<Duration>) -> Result<Input> {
+        // Stream urls are signed and expire, so the page is read again
+        let tralbum = tralbum(&self.uri).await.ok_or(Error::Metadata)?;
+        let url = tralbum
+            .get("trackinfo")
+            .and_then(Value::as_array)
+            .and_then(|tracks| tracks.first())
+            .and_then(stream_url)
+            .ok_or(Error::Metadata)?;
+        if let Some(time) = time {
+            let ts = format!("{:.3}", time.as_secs_f64());
+
+            stream::ffmpeg(url, &["-ss", &ts], self.metadata.clone())
+        } else {
+            stream::ffmpeg(url, &[], self.metadata.clone())
+        }
+    }
+}
+
+pub async fn bandcamp(uri: String, metadata: Metadata, lazy: bool) -> Result<Restartable> {
+    Restartable::new(BandcampRestarter { uri, metadata }, lazy).await
+}
+
+pub struct BandcampResolver;
+
+#[async_trait]
+impl Resolver for BandcampResolver {
+    fn name(&self) -> &'static str {
+        "bandcamp"
+    }
+
+    fn claims(&self, query: &str) -> bool {
+        Url::parse(query)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host == "bandcamp.com" || host.ends_with(".bandcamp.com")))
+            .unwrap_or(false)
+    }
+
+    async fn resolve(&self, query: &str) -> std::result::Result<Resolved, resolver::Error> {
+        let url = Url::parse(query).map_err(|_| resolver::Error::NothingFound)?;
+        let tralbum = tralbum(query).await.ok_or(resolver::Error::NothingFound)?;
+        let tracks = tralbum
+            .get("trackinfo")
+            .and_then(Value::as_array)
+            .ok_or(resolver::Error::NothingFound)?;
+
+        if url.path().starts_with("/track/") {
+            let track = tracks.first().ok_or(resolver::Error::NothingFound)?;
+            if stream_url(track).is_none() {
+                return Err(resolver::Error::NothingFound);
+            }
+            let metadata = metadata(&tralbum, track, query);
+            return Ok(Resolved::Track(bandcamp(query.to_string(), metadata, true).await?));
+        }
+
+        // Album pages list tracks in album order, each one is queued through its own page
+        let mut entries = Vec::new();
+        let mut unreleased = 0;
+        for track in tracks {
+            match track.get("title_link").and_then(Value::as_str) {
+                Some(link) if stream_url(track).is_some() => match url.join(link) {
+                    Ok(link) => entries.push(link.to_string()),
+                    Err(_) => unreleased += 1,
+                },
+                _ => unreleased += 1,
+            }
+        }
+        if entries.is_empty() {
+            return Err(resolver::Error::NothingFound);
+        }
+        Ok(Resolved::Playlist(resolver::Playlist {
+            entries: resolver::entries(entries),
+            skipped: if unreleased > 0 {
+                vec![(Skip::Unreleased, unreleased)]
+            } else {
+                Vec::new()
+            },
+        }))
+    }
+}
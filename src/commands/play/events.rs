@@ -7,18 +7,43 @@ use serenity::{
     model::prelude::{ChannelId, Message},
 };
 
-use songbird::{tracks::PlayMode, Event, EventContext, EventHandler};
+use log::warn;
+use songbird::{input::Metadata, tracks::PlayMode, Event, EventContext, EventHandler};
+
+use crate::locale::Lang;
 
 pub struct SongStart {
     channel_id: ChannelId,
     http: Arc<Http>,
+    lang: Lang,
+    autoplay: bool,
+    /// Position in the original track where the stream starts.
+    offset: Duration,
 }
 
 impl SongStart {
-    pub fn new(chan_id: ChannelId, ctx_http: Arc<Http>) -> Self {
+    pub fn new(chan_id: ChannelId, ctx_http: Arc<Http>, lang: Lang) -> Self {
         SongStart {
             channel_id: chan_id,
             http: ctx_http,
+            lang,
+            autoplay: false,
+            offset: Duration::ZERO,
+        }
+    }
+
+    pub fn autoplay(chan_id: ChannelId, ctx_http: Arc<Http>, lang: Lang) -> Self {
+        SongStart {
+            autoplay: true,
+            ..SongStart::new(chan_id, ctx_http, lang)
+        }
+    }
+
+    /// For recovered streams, which start mid-track.
+    pub fn resumed(chan_id: ChannelId, ctx_http: Arc<Http>, lang: Lang, offset: Duration) -> Self {
+        SongStart {
+            offset,
+            ..SongStart::new(chan_id, ctx_http, lang)
         }
     }
 }
@@ -29,14 +54,7 @@ impl EventHandler for SongStart {
         if let EventContext::Track(&[(_, track)]) = ctx {
             let metadata = track.metadata().clone();
 
-            let title = format!(
-                "`{}` by `{}`",
-                metadata.title.unwrap(),
-                match metadata.artist {
-                    Some(name) => name,
-                    None => metadata.channel.unwrap(),
-                }
-            );
+            let title = song_title(&metadata, self.lang);
             let duration = metadata.duration.unwrap_or(Duration::from_secs(0)).as_secs();
             let minutes_dur = duration / 60;
             let mut seconds_dur = (duration - minutes_dur * 60).to_string();
@@ -44,44 +62,66 @@ impl EventHandler for SongStart {
                 seconds_dur = "0".to_string() + &seconds_dur;
             }
 
-            let message = self
+            let minutes = self.offset.as_secs() / 60;
+            let mut seconds = (self.offset.as_secs() - minutes * 60).to_string();
+            if seconds.len() == 1 {
+                seconds = "0".to_string() + &seconds;
+            }
+
+            let message = match self
                 .channel_id
                 .send_message(&self.http, |response| {
                     response.add_embed(|embed| {
                         embed
                             .title(title)
-                            .thumbnail(metadata.thumbnail.unwrap())
+                            .thumbnail(metadata.thumbnail.unwrap_or_default())
                             .description(format!(
-                                    "⚪⚪⚪⚪⚪⚪⚪⚪⚪⚪ - `0:00/{}:{}`"
-                                    , minutes_dur,
+                                    "{} - `{}:{}/{}:{}`",
+                                    time_bar(self.offset, metadata.duration.unwrap_or_default()),
+                                    minutes,
+                                    seconds,
+                                    minutes_dur,
                                     seconds_dur)
                                 )
-                            .colour(16711937)
+                            .colour(16711937);
+                        if self.autoplay {
+                            embed.footer(|footer| footer.text(self.lang.tr("autoplay.footer")));
+                        }
+                        embed
                     })
                 })
                 .await
-                .unwrap();
-
-            track
-                .add_event(
-                    Event::Periodic(Duration::from_secs(5), None),
-                    Nowplaying {
-                        channel_id: self.channel_id,
-                        msg: message.clone(),
-                        http: self.http.clone(),
-                    },
-                )
-                .unwrap();
-
-            track
-                .add_event(
-                    Event::Track(songbird::TrackEvent::End),
-                    SongEnd {
-                        msg: message,
-                        http: self.http.clone(),
-                    },
-                )
-                .unwrap();
+            {
+                Ok(message) => message,
+                Err(e) => {
+                    warn!("Cannot announce song: {e}");
+                    return Some(Event::Cancel);
+                }
+            };
+
+            if let Err(e) = track.add_event(
+                Event::Periodic(Duration::from_secs(5), None),
+                Nowplaying {
+                    channel_id: self.channel_id,
+                    msg: message.clone(),
+                    http: self.http.clone(),
+                    lang: self.lang,
+                    autoplay: self.autoplay,
+                    offset: self.offset,
+                },
+            ) {
+                warn!("{e}");
+            }
+
+            if let Err(e) = track.add_event(
+                Event::Track(songbird::TrackEvent::End),
+                SongEnd {
+                    msg: message,
+                    http: self.http.clone(),
+                },
+            ) {
+                warn!("{e}");
+            }
 
             Some(Event::Cancel)
         } else {
@@ -90,6 +130,16 @@ impl EventHandler for SongStart {
     }
 }
 
+fn song_title(metadata: &Metadata, lang: Lang) -> String {
+    lang.tr_with(
+        "song.title",
+        &[
+            ("title", &metadata.title.clone().unwrap_or_default()),
+            ("artist", &metadata.artist.clone().or_else(|| metadata.channel.clone()).unwrap_or_default()),
+        ],
+    )
+}
+
 struct SongEnd {
     msg: Message,
     http: Arc<Http>,
@@ -99,7 +149,9 @@ struct SongEnd {
 impl EventHandler for SongEnd {
     async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
         if let EventContext::Track(&[(_, _)]) = ctx {
-            self.msg.delete(&self.http).await.unwrap();
+            if let Err(e) = self.msg.delete(&self.http).await {
+                warn!("{e}");
+            }
             Some(Event::Cancel)
         } else {
             None
@@ -111,6 +163,9 @@ struct Nowplaying {
     channel_id: ChannelId,
     msg: Message,
     http: Arc<Http>,
+    lang: Lang,
+    autoplay: bool,
+    offset: Duration,
 }
 
 #[async_trait]
@@ -120,14 +175,7 @@ impl EventHandler for Nowplaying {
             if state.playing == PlayMode::Play {
                 let metadata = track.metadata().clone();
 
-                let title = format!(
-                    "`{}` by `{}`",
-                    metadata.title.unwrap(),
-                    match metadata.artist {
-                        Some(name) => name,
-                        None => metadata.channel.unwrap(),
-                    }
-                );
+                let title = song_title(&metadata, self.lang);
                 let duration = metadata.duration.unwrap_or(Duration::from_secs(0)).as_secs();
                 let minutes_dur = duration / 60;
                 let mut seconds_dur = (duration - minutes_dur * 60).to_string();
@@ -135,8 +183,9 @@ impl EventHandler for Nowplaying {
                     seconds_dur = "0".to_string() + &seconds_dur;
                 }
 
-                let minutes = state.position.as_secs() / 60;
-                let mut seconds = (state.position.as_secs() - minutes * 60).to_string();
+                let position = self.offset + state.position;
+                let minutes = position.as_secs() / 60;
+                let mut seconds = (position.as_secs() - minutes * 60).to_string();
 
                 if seconds.len() == 1 {
                     seconds = "0".to_string() + &seconds;
@@ -145,23 +194,29 @@ impl EventHandler for Nowplaying {
                 let mut embed = CreateEmbed(HashMap::new());
                 embed
                     .title(title)
-                    .thumbnail(metadata.thumbnail.unwrap())
+                    .thumbnail(metadata.thumbnail.unwrap_or_default())
                     .description(format!(
                         "{} - `{}:{}/{}:{}`",
-                        time_bar(state.position, metadata.duration.unwrap()),
+                        time_bar(position, metadata.duration.unwrap_or_default()),
                         minutes,
                         seconds,
                         minutes_dur,
                         seconds_dur
                     ))
                     .colour(16711937);
+                if self.autoplay {
+                    embed.footer(|footer| footer.text(self.lang.tr("autoplay.footer")));
+                }
 
-                self.channel_id
+                if let Err(e) = self
+                    .channel_id
                     .edit_message(&self.http, self.msg.id, |response| {
                         response.set_embed(embed)
                     })
                     .await
-                    .unwrap();
+                {
+                    warn!("Cannot update now playing: {e}");
+                }
                 None
             } else {
                 None
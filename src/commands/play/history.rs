@@ -0,0 +1,133 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::warn;
+use rusqlite::{params, Connection, OptionalExtension};
+use serenity::model::prelude::{GuildId, UserId};
+
+use crate::db;
+
+const MAX_HISTORY: usize = 200;
+/// Plays further apart than this belong to different listening sessions.
+const SESSION_GAP: u64 = 30 * 60;
+
+#[derive(Clone)]
+pub struct Played {
+    pub title: String,
+    pub url: String,
+    pub user: UserId,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Stores the play in the background, nothing has to wait for it.
+pub fn record(guild_id: GuildId, played: Played) {
+    tokio::spawn(db::run(move |db| insert(db, guild_id, played)));
+}
+
+fn insert(db: &Connection, guild_id: GuildId, played: Played) {
+    let now = now();
+    let last: Option<(u64, u64)> = db
+        .query_row(
+            "SELECT session, played_at FROM plays WHERE guild_id = ?1 ORDER BY id DESC LIMIT 1",
+            params![guild_id.0],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .unwrap_or_default();
+    let session = match last {
+        Some((session, played_at)) if now.saturating_sub(played_at) < SESSION_GAP => session,
+        Some((session, _)) => session + 1,
+        None => 0,
+    };
+
+    if let Err(e) = db.execute(
+        "INSERT INTO plays (guild_id, user_id, url, title, session, played_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![guild_id.0, played.user.0, played.url, played.title, session, now],
+    ) {
+        warn!("Cannot record play: {e}");
+    }
+}
+
+/// Marks latest play of `url` as skipped.
+pub async fn skipped(guild_id: GuildId, url: String) {
+    let result = db::run(move |db| {
+        db.execute(
+            "UPDATE plays SET skipped = 1 WHERE id = (SELECT MAX(id) FROM plays WHERE guild_id = ?1 AND url = ?2)",
+            params![guild_id.0, url],
+        )
+    })
+    .await;
+    if let Err(e) = result {
+        warn!("Cannot record skip: {e}");
+    }
+}
+
+/// Returns whether track was liked, `false` when the like was already there.
+pub async fn like(guild_id: GuildId, user: UserId, url: String) -> bool {
+    let result = db::run(move |db| {
+        db.execute(
+            "INSERT OR IGNORE INTO likes (guild_id, user_id, url) VALUES (?1, ?2, ?3)",
+            params![guild_id.0, user.0, url],
+        )
+    })
+    .await;
+    match result {
+        Ok(changed) => changed > 0,
+        Err(e) => {
+            warn!("Cannot record like: {e}");
+            false
+        }
+    }
+}
+
+fn query(db: &Connection, sql: &str, params: impl rusqlite::Params) -> Vec<Played> {
+    let result = db.prepare(sql).and_then(|mut statement| {
+        statement
+            .query_map(params, |row| {
+                Ok(Played {
+                    title: row.get(0)?,
+                    url: row.get(1)?,
+                    user: UserId(row.get(2)?),
+                })
+            })?
+            .collect()
+    });
+    match result {
+        Ok(played) => played,
+        Err(e) => {
+            warn!("Cannot read history: {e}");
+            Vec::new()
+        }
+    }
+}
+
+/// Newest first history of given guild.
+pub async fn recent(guild_id: GuildId) -> Vec<Played> {
+    db::run(move |db| recent_in(db, guild_id)).await
+}
+
+/// [`recent`] for callers already holding the connection.
+pub(super) fn recent_in(db: &Connection, guild_id: GuildId) -> Vec<Played> {
+    query(
+        db,
+        "SELECT title, url, user_id FROM plays WHERE guild_id = ?1 ORDER BY id DESC LIMIT ?2",
+        params![guild_id.0, MAX_HISTORY],
+    )
+}
+
+/// Newest first tracks requested by `user` in any guild.
+pub async fn requested_by(user: UserId) -> Vec<Played> {
+    db::run(move |db| {
+        query(
+            db,
+            "SELECT title, url, user_id FROM plays WHERE user_id = ?1 ORDER BY id DESC LIMIT ?2",
+            params![user.0, MAX_HISTORY],
+        )
+    })
+    .await
+}
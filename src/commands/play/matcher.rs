@@ -0,0 +1,177 @@
+use std::sync::LazyLock;
+
+use log::trace;
+use tokio::sync::Semaphore;
+
+use super::{
+    resolver::TrackInfo,
+    youtube::{self, SearchResult},
+};
+
+const CANDIDATES: usize = 5;
+/// Searches running at once, each one is a yt-dlp process and playlists have hundreds of tracks.
+const MAX_SEARCHES: usize = 4;
+/// Search by ISRC mostly finds the exact upload, but needs a sanity check as results can be anything.
+const ISRC_MIN_SCORE: f64 = 20.0;
+/// Words marking a different recording than the studio one.
+const VERSION_WORDS: [&str; 3] = ["live", "cover", "remix"];
+
+static SEARCHES: LazyLock<Semaphore> = LazyLock::new(|| Semaphore::new(MAX_SEARCHES));
+
+async fn search(query: &str) -> Vec<SearchResult> {
+    // The semaphore is never closed
+    let _permit = SEARCHES.acquire().await.ok();
+    youtube::search(query, CANDIDATES).await
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn score(info: &TrackInfo, candidate: &SearchResult) -> f64 {
+    let mut score = 0.0;
+
+    match (info.duration, candidate.duration) {
+        (Some(expected), Some(actual)) => {
+            let diff = expected.as_secs_f64() - actual.as_secs_f64();
+            // Few seconds are normal between services, anything more is an intro or a different cut
+            score -= match diff.abs() {
+                d if d <= 3.0 => 0.0,
+                d if d <= 15.0 => d,
+                d => 15.0 + d * 2.0,
+            };
+        }
+        (_, None) => score -= 20.0,
+        _ => {}
+    }
+
+    let channel = candidate.channel.as_deref().unwrap_or_default().to_lowercase();
+    let artist = info.artist.to_lowercase();
+    if channel.ends_with(" - topic") {
+        score += 25.0;
+    }
+    if channel.trim_end_matches(" - topic") == artist || channel.replace(' ', "") == artist.replace(' ', "") + "vevo" {
+        score += 15.0;
+    } else if channel.contains(&artist) {
+        score += 8.0;
+    }
+
+    let original = words(&info.title);
+    let title = words(&candidate.title);
+    for word in VERSION_WORDS {
+        if title.iter().any(|w| w == word) && !original.iter().any(|w| w == word) {
+            score -= 30.0;
+        }
+    }
+    let found = original.iter().filter(|w| title.contains(w)).count();
+    score += 10.0 * found as f64 / original.len().max(1) as f64;
+
+    score
+}
+
+fn best(info: &TrackInfo, candidates: Vec<SearchResult>) -> Option<(f64, String)> {
+    candidates
+        .into_iter()
+        .map(|candidate| (score(info, &candidate), candidate))
+        .inspect(|(score, candidate)| trace!("{} - {:?} scored {score:.1}", candidate.title, candidate.channel))
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(score, candidate)| (score, candidate.url))
+}
+
+/// Searches yt for `info` and returns url of the candidate matching it best, trying its ISRC first.
+/// Without yt-dlp there is nothing to match against, the registry falls back to a plain query.
+pub async fn best_match(info: &TrackInfo) -> Option<String> {
+    if !youtube::available().await {
+        return None;
+    }
+    if let Some(isrc) = &info.isrc {
+        let by_isrc = search(&format!("\"{isrc}\"")).await;
+        match best(info, by_isrc) {
+            Some((score, url)) if score >= ISRC_MIN_SCORE => return Some(url),
+            _ => trace!("Nothing good found by ISRC {isrc}, searching by title"),
+        }
+    }
+    best(info, search(&info.query()).await).map(|(_, url)| url)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn info() -> TrackInfo {
+        TrackInfo {
+            title: "Around the World".to_string(),
+            artist: "Daft Punk".to_string(),
+            duration: Some(Duration::from_secs(429)),
+            isrc: None,
+        }
+    }
+
+    fn candidate(title: &str, channel: Option<&str>, secs: Option<u64>) -> SearchResult {
+        SearchResult {
+            url: title.to_string(),
+            title: title.to_string(),
+            channel: channel.map(str::to_string),
+            duration: secs.map(Duration::from_secs),
+            thumbnail: None,
+        }
+    }
+
+    #[test]
+    fn splits_words() {
+        assert_eq!(
+            words("Around the World (Live) - Daft Punk"),
+            ["around", "the", "world", "live", "daft", "punk"]
+        );
+        assert!(words(" - ").is_empty());
+    }
+
+    #[test]
+    fn prefers_topic_channels() {
+        let topic = candidate("Around the World", Some("Daft Punk - Topic"), Some(429));
+        let reupload = candidate("Around the World", Some("music uploads"), Some(429));
+        assert!(score(&info(), &topic) > score(&info(), &reupload));
+    }
+
+    #[test]
+    fn penalizes_different_versions() {
+        let studio = candidate("Daft Punk - Around the World", Some("Daft Punk"), Some(430));
+        let live = candidate("Daft Punk - Around the World (Live)", Some("Daft Punk"), Some(430));
+        let longer = candidate("Daft Punk - Around the World", Some("Daft Punk"), Some(480));
+        assert!(score(&info(), &studio) > score(&info(), &live));
+        assert!(score(&info(), &studio) > score(&info(), &longer));
+    }
+
+    #[test]
+    fn keeps_versions_asked_for() {
+        let mut info = info();
+        info.title = "Around the World (Live)".to_string();
+        let live = candidate("Around the World (Live)", Some("Daft Punk"), Some(429));
+        let studio = candidate("Around the World", Some("Daft Punk"), Some(429));
+        assert!(score(&info, &live) >= score(&info, &studio));
+    }
+
+    #[test]
+    fn handles_unknown_channel_and_duration() {
+        let unknown = candidate("Around the World", None, None);
+        assert!(score(&info(), &unknown) < score(&info(), &candidate("Around the World", None, Some(429))));
+    }
+
+    #[test]
+    fn picks_best_candidate() {
+        let candidates = vec![
+            candidate("Around the World (cover)", Some("someone"), Some(429)),
+            candidate("Daft Punk - Around the World", Some("DaftPunkVEVO"), Some(431)),
+            candidate("Around", Some("someone else"), None),
+        ];
+        let (_, url) = best(&info(), candidates).unwrap();
+        assert_eq!(url, "Daft Punk - Around the World");
+        assert!(best(&info(), Vec::new()).is_none());
+    }
+}
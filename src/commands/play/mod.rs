@@ -1,172 +1,448 @@
 use serenity::{
+    async_trait,
     builder::CreateApplicationCommand,
-    model::{prelude::interaction::application_command::ApplicationCommandInteraction, user::User},
-    prelude::Context,
+    model::{
+        prelude::{
+            interaction::{
+                application_command::ApplicationCommandInteraction, autocomplete::AutocompleteInteraction,
+                message_component::MessageComponentInteraction, InteractionResponseType,
+            },
+            ChannelId, GuildId, InteractionId, UserId,
+        },
+        user::User,
+    },
+    prelude::{Context, Mutex, TypeMapKey},
 };
 
-use log::trace;
-use songbird::{create_player, input::Metadata, Event};
+use log::{trace, warn};
+use songbird::{
+    create_player,
+    input::{Metadata, Restartable},
+    tracks::{Track, TrackHandle},
+    Call, Event, Songbird, TrackEvent,
+};
 
-use super::send_msg;
-use crate::commands::edit_msg;
-use std::time::Duration;
+use super::{
+    edit_msg, guild_id, manager, registry::Command, send_msg, string_option, truncate, user_channel,
+};
+use crate::{
+    errors::{Error, Result},
+    locale::{Lang, Localize},
+    settings::{self, Settings},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant},
+};
 
+pub mod autoplay;
+mod bandcamp;
 mod rustube;
 mod events;
-mod spotify;
-mod youtube;
+pub mod history;
+mod matcher;
+pub mod recommend;
+pub mod podcast;
+mod recovery;
+pub mod resolver;
+mod services;
+pub mod session;
+mod soundcloud;
+pub mod spotify;
+mod stream;
+pub mod youtube;
 
 use events::SongStart;
+use recovery::StreamRecovery;
+use resolver::{Resolved, REGISTRY};
 
-pub async fn run(interaction: &ApplicationCommandInteraction, ctx: &Context) {
-    let cache = &ctx.cache;
+/// Discord drops autocomplete responses after 3 seconds, search has to fit in what is left.
+const SEARCH_DEADLINE: Duration = Duration::from_millis(2000);
+const MAX_CHOICES: usize = 25;
+/// Shorter queries match too much to be worth a search.
+const MIN_SEARCH_LEN: usize = 3;
+/// Keystrokes closer together than this only search for the last one.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+const SEARCH_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+const SEARCH_CACHE_SIZE: usize = 500;
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
 
-    let uri = interaction.data.options[0]
-        .value
-        .clone()
-        .unwrap()
-        .as_str()
+/// Client every resolver and stream goes through, so connections are pooled between them.
+pub(crate) static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+        .timeout(HTTP_TIMEOUT)
+        .build()
+        .unwrap_or_else(|e| {
+            warn!("Cannot build http client, using the default one: {e}");
+            reqwest::Client::new()
+        })
+});
+
+/// Results of recent autocomplete searches, as typing and deleting repeats the same queries.
+static SEARCHES: LazyLock<std::sync::Mutex<HashMap<String, (Instant, Vec<(String, String)>)>>> =
+    LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+/// Latest autocomplete interaction of each user.
+static TYPING: LazyLock<std::sync::Mutex<HashMap<UserId, InteractionId>>> =
+    LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+
+fn cached_search(query: &str) -> Option<Vec<(String, String)>> {
+    SEARCHES
+        .lock()
         .unwrap()
-        .to_string();
-
-    let user = &interaction.user;
-    let user_id = user.id;
-    let guild_id = interaction.guild_id.expect("Not in channel");
-    let guild = cache.guild(guild_id).unwrap();
-
-    let voice_channel = guild
-        .voice_states
-        .get(&user_id)
-        .and_then(|voice_state| voice_state.channel_id);
-
-    let menager = songbird::get(ctx)
-        .await
-        .expect("Failed to get manager")
-        .clone();
-
-    if let Some(voice_channel) = voice_channel {
-        let handler_lock = menager.get_or_insert(guild_id);
-        send_msg(ctx, interaction, "Processing please wait").await;
-        if uri.contains("https://") {
-            if uri.contains("playlist") {
-                let playlist = match uri.contains("spotify") {
-                    true => spotify::playlist(&uri).await,
-                    false => Some(rustube::get_playlist(&uri).await.links),
+        .get(query)
+        .filter(|(searched, _)| searched.elapsed() < SEARCH_CACHE_TTL)
+        .map(|(_, results)| results.clone())
+}
+
+fn cache_search(query: String, results: Vec<(String, String)>) {
+    let mut searches = SEARCHES.lock().unwrap();
+    searches.retain(|_, (searched, _)| searched.elapsed() < SEARCH_CACHE_TTL);
+    if searches.len() >= SEARCH_CACHE_SIZE {
+        searches.clear();
+    }
+    searches.insert(query, (Instant::now(), results));
+}
+
+/// Waits a moment, returning whether `interaction` is still the latest one of its user.
+async fn debounce(interaction: &AutocompleteInteraction) -> bool {
+    let user = interaction.user.id;
+    TYPING.lock().unwrap().insert(user, interaction.id);
+    tokio::time::sleep(DEBOUNCE).await;
+    let mut typing = TYPING.lock().unwrap();
+    if typing.get(&user) == Some(&interaction.id) {
+        typing.remove(&user);
+        true
+    } else {
+        false
+    }
+}
+
+async fn search_choices(interaction: &AutocompleteInteraction, typed: &str) -> Vec<(String, String)> {
+    if typed.chars().count() < MIN_SEARCH_LEN {
+        return Vec::new();
+    }
+    if let Some(results) = cached_search(typed) {
+        return results;
+    }
+    if !debounce(interaction).await {
+        trace!("Skipping autocomplete search for {typed}, user kept typing");
+        return Vec::new();
+    }
+    match tokio::time::timeout(SEARCH_DEADLINE, youtube::search(typed, 5)).await {
+        Ok(results) => {
+            let results: Vec<(String, String)> = results.into_iter().map(|r| (r.title, r.url)).collect();
+            cache_search(typed.to_string(), results.clone());
+            results
+        }
+        Err(_) => {
+            trace!("Autocomplete search for {typed} timed out");
+            Vec::new()
+        }
+    }
+}
+
+/// Who queued a track and where it is announced, kept in the track's typemap so sessions can be saved.
+#[derive(Clone, Copy)]
+pub struct Queued {
+    /// `None` for tracks picked by autoplay.
+    pub requester: Option<UserId>,
+    pub announce: ChannelId,
+    /// Position in the original track where current stream starts, recovered streams start mid-track.
+    pub offset: Duration,
+    /// Language the track is announced in, so recovered and autoplayed tracks keep it.
+    pub lang: Lang,
+}
+
+impl TypeMapKey for Queued {
+    type Value = Queued;
+}
+
+impl Queued {
+    pub async fn of(track: &TrackHandle) -> Option<Queued> {
+        track.typemap().read().await.get::<Queued>().copied()
+    }
+
+    pub async fn attach(self, track: &TrackHandle) {
+        track.typemap().write().await.insert::<Queued>(self);
+    }
+}
+
+/// Creates track which announces itself and recovers its stream, as every queued track should.
+async fn player(
+    ctx: &Context,
+    manager: &Arc<Songbird>,
+    guild_id: GuildId,
+    settings: &Settings,
+    queued: Queued,
+    source: Restartable,
+) -> Result<(Track, TrackHandle)> {
+    let (mut track, track_handle) = create_player(source.into());
+    track.set_volume(settings.volume());
+    track_handle.add_event(
+        Event::Periodic(Duration::from_secs(0), None),
+        SongStart::new(queued.announce, ctx.http.clone(), queued.lang),
+    )?;
+    track_handle.add_event(
+        Event::Track(TrackEvent::End),
+        StreamRecovery::new(guild_id, queued.announce, ctx.http.clone(), manager.clone()),
+    )?;
+    queued.attach(&track_handle).await;
+    Ok((track, track_handle))
+}
+
+pub async fn enqueue(
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    handler_lock: &Arc<Mutex<Call>>,
+    voice_channel: ChannelId,
+    source: Restartable,
+) -> Result<Metadata> {
+    let guild_id = guild_id(interaction)?;
+    let settings = settings::get(guild_id);
+    let announce = settings.announce_channel.unwrap_or(interaction.channel_id);
+    let manager = manager(ctx).await?;
+
+    let mut handler = handler_lock.lock().await;
+    if handler.queue().len() >= settings.queue() {
+        return Err(Error::QueueFull);
+    }
+
+    let queued = Queued {
+        requester: Some(interaction.user.id),
+        announce,
+        offset: Duration::ZERO,
+        lang: Lang::of(interaction),
+    };
+    let (track, track_handle) = player(ctx, &manager, guild_id, &settings, queued, source).await?;
+
+    let metadata = track_handle.metadata().clone();
+    if let (Some(title), Some(url)) = (metadata.title.clone(), metadata.source_url.clone()) {
+        history::record(guild_id, history::Played { title, url, user: interaction.user.id });
+    }
+
+    handler.join(voice_channel).await?;
+    autoplay::watch(
+        handler_lock,
+        &mut handler,
+        guild_id,
+        ctx.http.clone(),
+        manager,
+        ctx.cache.current_user_id(),
+    );
+    handler.enqueue(track);
+    Ok(metadata)
+}
+
+pub fn get_msg(metadata: Metadata, user: &User, lang: Lang) -> String {
+    let content = lang.tr_with(
+        "play.added",
+        &[
+            ("user", &user.name),
+            ("title", &metadata.title.unwrap_or_default()),
+            ("artist", &metadata.artist.or(metadata.channel).unwrap_or_default()),
+        ],
+    );
+    content
+}
+
+/// Queues what was picked from a select menu, `load` gets the index of the picked option and
+/// returns the source or the message explaining why it cannot be played.
+pub async fn queue_selected<F, Fut>(
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    selected: &MessageComponentInteraction,
+    voice_channel: ChannelId,
+    nothing_selected: &str,
+    load: F,
+) -> Result<()>
+where
+    F: FnOnce(usize) -> Fut,
+    Fut: Future<Output = std::result::Result<Restartable, String>>,
+{
+    let lang = Lang::of(interaction);
+    // Loading and joining take longer than Discord waits for an answer
+    selected
+        .create_interaction_response(&ctx.http, |response| {
+            response.kind(InteractionResponseType::DeferredUpdateMessage)
+        })
+        .await?;
+
+    let index = selected.data.values.first().and_then(|value| value.parse::<usize>().ok());
+    let content = match index {
+        Some(index) => match load(index).await {
+            Ok(source) => {
+                let queued = async {
+                    let handler_lock = manager(ctx).await?.get_or_insert(guild_id(interaction)?);
+                    enqueue(ctx, interaction, &handler_lock, voice_channel, source).await
+                }
+                .await;
+                match queued {
+                    Ok(metadata) => get_msg(metadata, &interaction.user, lang),
+                    Err(e) => e.message(lang),
+                }
+            }
+            Err(message) => message,
+        },
+        None => lang.tr(nothing_selected),
+    };
+    trace!("{content}");
+
+    selected
+        .edit_original_interaction_response(&ctx.http, |response| {
+            response.content(content).set_embeds(Vec::new()).components(|c| c)
+        })
+        .await?;
+    Ok(())
+}
+
+pub struct Play;
+
+#[async_trait]
+impl Command for Play {
+    fn name(&self) -> &'static str {
+        "play"
+    }
+
+    fn register<'a>(&self, command: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        command
+            .create_option(|option| {
+                option
+                    .localized_name("query", "command.play.query")
+                    .localized_description("command.play.query")
+                    .kind(serenity::model::prelude::command::CommandOptionType::String)
+                    .required(true)
+                    .set_autocomplete(true)
+            })
+    }
+
+    async fn run(&self, interaction: &ApplicationCommandInteraction, ctx: &Context) -> Result<()> {
+        let uri = string_option(interaction, "query");
+        let user = &interaction.user;
+        let guild_id = guild_id(interaction)?;
+        let voice_channel = user_channel(ctx, interaction)?;
+        let lang = Lang::of(interaction);
+
+        let handler_lock = manager(ctx).await?.get_or_insert(guild_id);
+        send_msg(ctx, interaction, &lang.tr("processing")).await?;
+        match REGISTRY.resolve(&uri).await? {
+            Resolved::Playlist(playlist) => {
+                let limit = settings::get(guild_id).playlist_songs();
+                let mut vec = playlist.entries;
+                let over = vec.len().saturating_sub(limit);
+                let mut reasons: Vec<String> = playlist
+                    .skipped
+                    .iter()
+                    .map(|(reason, count)| lang.tr_with(reason.key(), &[("count", count)]))
+                    .collect();
+                if over > 0 {
+                    reasons.push(lang.tr_with("play.over_limit", &[("over", &over), ("limit", &limit)]));
+                }
+                let count = over + playlist.skipped.iter().map(|(_, count)| count).sum::<usize>();
+                let skipped = (count > 0)
+                    .then(|| lang.tr_with("play.skipped", &[("count", &count), ("reasons", &reasons.join(", "))]));
+                vec.truncate(limit);
+                let found = lang.tr_with("play.found", &[("count", &vec.len())]);
+                let found = match &skipped {
+                    Some(skipped) => format!("{found}\n{skipped}"),
+                    None => found,
                 };
-                if let Some(vec) = playlist {
-                    let mut msg = edit_msg(ctx, interaction, &format!("Found {} songs", vec.len())).await;
-                    let len = vec.len();
-                    let mut succes = 0; 
-                    let mut failed = 0;
-                    for (index, song) in vec.into_iter().enumerate() {
-                        let source = match song.contains("https://") {
-                            true => match rustube::rustube(song, true).await {
-                                Ok(src) => src,
-                                Err(_) => {
-                                    failed += 1;
-                                    continue
-                                },
-                            },
-                            false => match rustube::rustube_search(song, true).await {
-                                Ok(src) => src,
-                                Err(_) => {
-                                    failed += 1;
-                                    continue
-                                }
-                            },
-                        };
-
-                        let (track, track_handle) = create_player(source.into());
-                        track_handle
-                            .add_event(
-                                Event::Periodic(Duration::from_secs(0), None),
-                                SongStart::new(interaction.channel_id, ctx.http.clone()),
-                            )
-                            .unwrap();
-                        
-                        let metadata = track_handle.metadata().clone();
-                        succes += 1;
-                        let msg_content = format!("`Loading... {}/{}`\n`Ok: {} | Failed: {}`", index+1, len, succes , failed);
-                        let content = get_msg(metadata, user);
-                        trace!("{content}");
-
-                        let mut handler = handler_lock.lock().await;
-                        handler.join(voice_channel).await.unwrap();
-                        handler.enqueue(track);
-                        msg.edit(ctx.http.clone(), |m| m.content(msg_content)).await.unwrap();
+                let mut msg = edit_msg(ctx, interaction, &found).await?;
+                let len = vec.len();
+                let mut succes = 0; 
+                let mut failed = 0;
+                for (index, song) in vec.into_iter().enumerate() {
+                    let source = match REGISTRY.resolve_entry(&song).await {
+                        Ok(src) => src,
+                        Err(_) => {
+                            failed += 1;
+                            continue
+                        },
+                    };
+
+                    let metadata = match enqueue(ctx, interaction, &handler_lock, voice_channel, source).await {
+                        Ok(metadata) => metadata,
+                        Err(Error::QueueFull) => {
+                            let content = lang.tr_with(
+                                "play.queue_full",
+                                &[("added", &succes), ("skipped", &(len - index))],
+                            );
+                            msg.edit(ctx.http.clone(), |m| m.content(content)).await?;
+                            break;
+                        }
+                        Err(e) => {
+                            warn!("Cannot queue playlist entry: {e:?}");
+                            failed += 1;
+                            continue;
+                        }
+                    };
+                    succes += 1;
+                    let mut msg_content = lang.tr_with(
+                        "play.loading",
+                        &[("index", &(index + 1)), ("total", &len), ("ok", &succes), ("failed", &failed)],
+                    );
+                    if let Some(skipped) = &skipped {
+                        msg_content += &format!("\n`{skipped}`");
                     }
-                } else {
-                    edit_msg(ctx, interaction, "Invalid url").await;
+                    let content = get_msg(metadata, user, lang);
+                    trace!("{content}");
+
+                    msg.edit(ctx.http.clone(), |m| m.content(msg_content)).await?;
                 }
-            } else if let Ok(source) = rustube::rustube(uri, true).await {
-                let (track, track_handle) = create_player(source.into());
-                track_handle
-                    .add_event(
-                        Event::Periodic(Duration::from_secs(0), None),
-                        SongStart::new(interaction.channel_id, ctx.http.clone()),
-                    )
-                    .unwrap();
-
-                let metadata = track_handle.metadata().clone();
-                let content = get_msg(metadata, user);
+            }
+            Resolved::Track(source) => {
+                let metadata = enqueue(ctx, interaction, &handler_lock, voice_channel, source).await?;
+                let content = get_msg(metadata, user, lang);
                 trace!("{content}");
 
-                let mut handler = handler_lock.lock().await;
-                handler.join(voice_channel).await.unwrap();
-                handler.enqueue(track);
-
-                edit_msg(ctx, interaction, &content).await;
-            } else {
-                edit_msg(ctx, interaction, "Nothing found").await;
+                edit_msg(ctx, interaction, &content).await?;
             }
-        } else if let Ok(source) = rustube::rustube_search(uri, true).await {
-            let (track, track_handle) = create_player(source.into());
-            track_handle
-                .add_event(
-                    Event::Periodic(Duration::from_secs(0), None),
-                    SongStart::new(interaction.channel_id, ctx.http.clone()),
-                )
-                .unwrap();
-
-            let metadata = track_handle.metadata().clone();
-            let content = get_msg(metadata, user);
-            trace!("{content}");
-
-            let mut handler = handler_lock.lock().await;
-            handler.join(voice_channel).await.unwrap();
-            handler.enqueue(track);
-
-            edit_msg(ctx, interaction, &content).await;
-        } else {
-            edit_msg(ctx, interaction, "Nothing found").await;
         }
-    } else {
-        send_msg(ctx, interaction, "Not in voice channel").await
+        Ok(())
     }
-}
 
-pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
-    command
-        .name("play")
-        .description("play song from yt")
-        .create_option(|option| {
-            option
-                .name("query")
-                .description("Insert video/playlist name or url")
-                .kind(serenity::model::prelude::command::CommandOptionType::String)
-                .required(true)
-        })
-}
+    async fn autocomplete(&self, interaction: &AutocompleteInteraction, ctx: &Context) {
+        let typed = interaction
+            .data
+            .options
+            .iter()
+            .find(|option| option.focused)
+            .and_then(|option| option.value.as_ref())
+            .and_then(|value| value.as_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        let matches = |played: &history::Played| played.title.to_lowercase().contains(&typed);
+        let recent = match interaction.guild_id {
+            Some(guild_id) => history::recent(guild_id).await,
+            None => Vec::new(),
+        };
+        let mut choices: Vec<(String, String)> = history::requested_by(interaction.user.id)
+            .await
+            .into_iter()
+            .filter(matches)
+            .chain(recent.into_iter().filter(matches))
+            .map(|played| (played.title, played.url))
+            .collect();
+
+        choices.extend(search_choices(interaction, &typed).await);
 
-fn get_msg(metadata: Metadata, user: &User) -> String {
-    let content = format!(
-        "{} added `{}` by `{}`",
-        user.name,
-        metadata.title.unwrap(),
-        match metadata.artist {
-            Some(name) => name,
-            None => metadata.channel.unwrap(),
+        let mut seen = HashSet::new();
+        choices.retain(|(_, url)| url.len() <= 100 && seen.insert(url.clone()));
+        choices.truncate(MAX_CHOICES);
+
+        if let Err(e) = interaction
+            .create_autocomplete_response(&ctx.http, |response| {
+                for (title, url) in choices {
+                    response.add_string_choice(truncate(&title, 100), url);
+                }
+                response
+            })
+            .await
+        {
+            warn!("Cannot respond to autocomplete: {e}");
         }
-    );
-    content
+    }
 }
@@ -0,0 +1,158 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use log::{trace, warn};
+use serenity::async_trait;
+use songbird::input::{error::Result, restartable::Restart, Codec, Container, Input, Metadata, Restartable};
+
+use super::{stream, CLIENT};
+
+const CACHE_DIR: &str = "./cache/podcasts";
+const CACHE_TTL: Duration = Duration::from_secs(30 * 60);
+pub const MAX_EPISODES: usize = 25;
+
+pub struct Feed {
+    pub title: String,
+    pub episodes: Vec<Episode>,
+}
+
+#[derive(Clone)]
+pub struct Episode {
+    pub title: Option<String>,
+    pub url: String,
+    pub podcast: String,
+    pub thumbnail: Option<String>,
+    pub duration: Option<Duration>,
+    pub published: Option<String>,
+}
+
+impl Episode {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            track: None,
+            artist: Some(self.podcast.clone()),
+            date: self.published.clone(),
+            channels: Some(2),
+            channel: Some(self.podcast.clone()),
+            start_time: None,
+            duration: self.duration,
+            sample_rate: None,
+            source_url: Some(self.url.clone()),
+            title: Some(self.title.clone().unwrap_or_else(|| self.podcast.clone())),
+            thumbnail: self.thumbnail.clone(),
+        }
+    }
+}
+
+struct PodcastRestarter {
+    episode: Episode,
+}
+
+#[async_trait]
+impl Restart for PodcastRestarter {
+    async fn lazy_init(&mut self) -> Result<(Option<Metadata>, Codec, Container)> {
+        Ok((Some(self.episode.metadata()), Codec::FloatPcm, Container::Raw))
+    }
+
+    async fn call_restart(&mut self, time: Option<Duration>) -> Result<Input> {
+        if let Some(time) = time {
+            let ts = format!("{:.3}", time.as_secs_f64());
+
+            stream::ffmpeg(&self.episode.url, &["-ss", &ts], self.episode.metadata())
+        } else {
+            stream::ffmpeg(&self.episode.url, &[], self.episode.metadata())
+        }
+    }
+}
+
+pub async fn episode(episode: Episode, lazy: bool) -> Result<Restartable> {
+    Restartable::new(PodcastRestarter { episode }, lazy).await
+}
+
+fn cache_path(uri: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    uri.hash(&mut hasher);
+    PathBuf::from(CACHE_DIR).join(format!("{:x}.xml", hasher.finish()))
+}
+
+/// Returns feed body from local cache when it is fresh enough, downloading it otherwise.
+async fn fetch(uri: &str) -> Option<Vec<u8>> {
+    let path = cache_path(uri);
+    let fresh = tokio::fs::metadata(&path)
+        .await
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map(|age| age < CACHE_TTL)
+        .unwrap_or(false);
+    if fresh {
+        if let Ok(body) = tokio::fs::read(&path).await {
+            trace!("Using cached feed {uri}");
+            return Some(body);
+        }
+    }
+
+    let body = match CLIENT.get(uri).send().await.and_then(|r| r.error_for_status()) {
+        Ok(response) => response.bytes().await.ok().map(|b| b.to_vec()),
+        Err(e) => {
+            warn!("Cannot download feed {uri}: {e}");
+            None
+        }
+    };
+    match body {
+        Some(body) => {
+            if let Err(e) = tokio::fs::create_dir_all(CACHE_DIR).await {
+                warn!("Cannot create feed cache: {e}");
+            } else if let Err(e) = tokio::fs::write(&path, &body).await {
+                warn!("Cannot cache feed {uri}: {e}");
+            }
+            Some(body)
+        }
+        // Stale feed is better than nothing
+        None => tokio::fs::read(&path).await.ok(),
+    }
+}
+
+pub async fn feed(uri: &str) -> Option<Feed> {
+    let body = fetch(uri).await?;
+    let feed = match feed_rs::parser::parse(&body[..]) {
+        Ok(feed) => feed,
+        Err(e) => {
+            warn!("Cannot parse feed {uri}: {e}");
+            return None;
+        }
+    };
+
+    let title = feed.title.map(|t| t.content).unwrap_or_else(|| uri.to_string());
+    let artwork = feed.logo.or(feed.icon).map(|image| image.uri);
+    let episodes = feed
+        .entries
+        .into_iter()
+        .filter_map(|entry| {
+            let media = entry
+                .media
+                .iter()
+                .find(|media| media.content.iter().any(|content| content.url.is_some()))?;
+            let content = media.content.iter().find(|content| content.url.is_some())?;
+            Some(Episode {
+                title: entry.title.map(|t| t.content),
+                url: content.url.as_ref()?.to_string(),
+                podcast: title.clone(),
+                thumbnail: media
+                    .thumbnails
+                    .first()
+                    .map(|thumbnail| thumbnail.image.uri.clone())
+                    .or_else(|| artwork.clone()),
+                duration: media.duration.or(content.duration),
+                published: entry.published.map(|date| date.format("%Y-%m-%d").to_string()),
+            })
+        })
+        .take(MAX_EPISODES)
+        .collect();
+
+    Some(Feed { title, episodes })
+}
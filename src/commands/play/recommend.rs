@@ -0,0 +1,127 @@
+use std::collections::{HashMap, HashSet};
+
+use log::warn;
+use rusqlite::{params, Connection};
+use serenity::model::prelude::GuildId;
+
+use super::history;
+use crate::db;
+
+/// How many of the latest plays are used as seeds.
+const SEEDS: usize = 5;
+/// Tracks played this recently are never suggested again.
+const RECENT: usize = 30;
+const LIKE_WEIGHT: f64 = 2.0;
+const SKIP_WEIGHT: f64 = 3.0;
+
+pub struct Suggestion {
+    pub title: String,
+    pub url: String,
+    pub score: f64,
+}
+
+#[derive(Default)]
+struct Stats {
+    title: String,
+    plays: u64,
+    skips: u64,
+    likes: u64,
+    together: u64,
+}
+
+/// Suggests tracks for given guild from its own listening history.
+///
+/// Tracks played in the same sessions as the latest plays score highest, likes push tracks
+/// up and frequent skips push them down. Without any overlap the most liked and least
+/// skipped tracks of the guild are suggested instead.
+pub async fn suggest(guild_id: GuildId, count: usize) -> Vec<Suggestion> {
+    db::run(move |db| rank(db, guild_id, count)).await
+}
+
+fn rank(db: &Connection, guild_id: GuildId, count: usize) -> Vec<Suggestion> {
+    let recent = history::recent_in(db, guild_id);
+    let seeds: Vec<String> = recent.iter().take(SEEDS).map(|played| played.url.clone()).collect();
+    let excluded: HashSet<String> = recent.into_iter().take(RECENT).map(|played| played.url).collect();
+
+    let stats = match stats(db, guild_id, &seeds) {
+        Ok(stats) => stats,
+        Err(e) => {
+            warn!("Cannot read listening stats: {e}");
+            return Vec::new();
+        }
+    };
+
+    let mut suggestions: Vec<Suggestion> = stats
+        .into_iter()
+        .filter(|(url, _)| !excluded.contains(url))
+        .map(|(url, stats)| {
+            let skip_rate = stats.skips as f64 / stats.plays.max(1) as f64;
+            let score = stats.together as f64 * 10.0 + (stats.plays as f64).ln_1p()
+                + stats.likes as f64 * LIKE_WEIGHT
+                - skip_rate * SKIP_WEIGHT;
+            Suggestion {
+                title: stats.title,
+                url,
+                score,
+            }
+        })
+        .collect();
+    suggestions.sort_by(|a, b| b.score.total_cmp(&a.score));
+    suggestions.truncate(count);
+    suggestions
+}
+
+fn stats(db: &Connection, guild_id: GuildId, seeds: &[String]) -> rusqlite::Result<HashMap<String, Stats>> {
+    let mut stats: HashMap<String, Stats> = HashMap::new();
+
+    let mut statement = db.prepare(
+        "SELECT url, MAX(title), COUNT(*), SUM(skipped) FROM plays WHERE guild_id = ?1 GROUP BY url",
+    )?;
+    let rows = statement.query_map(params![guild_id.0], |row| {
+        Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+    })?;
+    for row in rows {
+        let (url, title, plays, skips) = row?;
+        stats.insert(
+            url,
+            Stats {
+                title,
+                plays,
+                skips,
+                ..Default::default()
+            },
+        );
+    }
+
+    let mut statement = db.prepare("SELECT url, COUNT(*) FROM likes WHERE guild_id = ?1 GROUP BY url")?;
+    let rows = statement.query_map(params![guild_id.0], |row| {
+        Ok((row.get::<_, String>(0)?, row.get(1)?))
+    })?;
+    for row in rows {
+        let (url, likes) = row?;
+        if let Some(stats) = stats.get_mut(&url) {
+            stats.likes = likes;
+        }
+    }
+
+    // Number of sessions each track shares with any of the seeds
+    let mut statement = db.prepare(
+        "SELECT b.url, COUNT(DISTINCT b.session) FROM plays a
+         JOIN plays b ON a.guild_id = b.guild_id AND a.session = b.session AND a.url != b.url
+         WHERE a.guild_id = ?1 AND a.url = ?2
+         GROUP BY b.url",
+    )?;
+    for seed in seeds {
+        let rows = statement.query_map(params![guild_id.0, seed], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
+        })?;
+        for row in rows {
+            let (url, together) = row?;
+            if let Some(stats) = stats.get_mut(&url) {
+                stats.together += together;
+            }
+        }
+    }
+
+    Ok(stats)
+}
@@ -0,0 +1,125 @@
+use std::{sync::Arc, time::Duration};
+
+use log::{info, warn};
+use serenity::{async_trait, http::Http, model::prelude::{ChannelId, GuildId}};
+use songbird::{create_player, tracks::PlayMode, Event, EventContext, EventHandler, Songbird, TrackEvent};
+
+use super::{
+    events::SongStart,
+    resolver::Resolver,
+    rustube::{self, RustubeResolver},
+    Queued,
+};
+use crate::locale::Lang;
+
+/// Tracks ending closer than this to their duration just finished.
+const END_TOLERANCE: Duration = Duration::from_secs(5);
+const MAX_RECOVERIES: usize = 3;
+
+/// Restarts yt streams which ended before their duration, usually because googlevideo url
+/// expired or the connection dropped.
+pub struct StreamRecovery {
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    http: Arc<Http>,
+    manager: Arc<Songbird>,
+    /// Position in the original track where current stream starts.
+    offset: Duration,
+    attempt: usize,
+}
+
+impl StreamRecovery {
+    pub fn new(guild_id: GuildId, channel_id: ChannelId, http: Arc<Http>, manager: Arc<Songbird>) -> Self {
+        StreamRecovery {
+            guild_id,
+            channel_id,
+            http,
+            manager,
+            offset: Duration::ZERO,
+            attempt: 0,
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for StreamRecovery {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let (state, track) = match ctx {
+            EventContext::Track(&[(state, track)]) => (state, track),
+            _ => return None,
+        };
+        // Stopped or skipped by someone
+        if state.playing != PlayMode::End {
+            return None;
+        }
+        let metadata = track.metadata();
+        let uri = metadata.source_url.as_deref()?;
+        let position = self.offset + state.position;
+        let duration = metadata.duration?;
+        if position + END_TOLERANCE >= duration || !RustubeResolver.claims(uri) {
+            return None;
+        }
+        let title = metadata.title.as_deref().unwrap_or(uri);
+        if self.attempt >= MAX_RECOVERIES {
+            warn!("Giving up on {title} after {MAX_RECOVERIES} recoveries");
+            return None;
+        }
+        info!(
+            "Stream of {title} ended at {}s of {}s, recovering",
+            position.as_secs(),
+            duration.as_secs()
+        );
+
+        let input = match rustube::resume(uri, position).await {
+            Ok(input) => input,
+            Err(e) => {
+                warn!("Cannot recover {title}: {e:?}");
+                return None;
+            }
+        };
+        let queued = Queued::of(track).await;
+        let lang = queued.map_or_else(|| Lang::for_guild(self.guild_id), |queued| queued.lang);
+        let (mut resumed, resumed_handle) = create_player(input);
+        // Keeps volume changed with /settings while the track was playing
+        resumed.set_volume(state.volume);
+        if let Err(e) = resumed_handle.add_event(
+            Event::Periodic(Duration::from_secs(0), None),
+            SongStart::resumed(self.channel_id, self.http.clone(), lang, position),
+        ) {
+            warn!("{e}");
+        }
+        if let Err(e) = resumed_handle.add_event(
+            Event::Track(TrackEvent::End),
+            StreamRecovery {
+                guild_id: self.guild_id,
+                channel_id: self.channel_id,
+                http: self.http.clone(),
+                manager: self.manager.clone(),
+                offset: position,
+                attempt: self.attempt + 1,
+            },
+        ) {
+            warn!("{e}");
+        }
+        if let Some(queued) = queued {
+            Queued { offset: position, ..queued }.attach(&resumed_handle).await;
+        }
+
+        // Queue drops the ended track after this handler, so resumed one goes right behind it.
+        // When the ended track is already gone the next one is playing, resumed one goes behind that.
+        let handler_lock = self.manager.get(self.guild_id)?;
+        let mut handler = handler_lock.lock().await;
+        handler.enqueue(resumed);
+        let ended = track.uuid();
+        handler.queue().modify_queue(|queue| {
+            let index = queue
+                .iter()
+                .position(|queued| queued.uuid() == ended)
+                .map_or(1, |index| index + 1);
+            if let Some(resumed) = queue.pop_back() {
+                queue.insert(index.min(queue.len()), resumed);
+            }
+        });
+        None
+    }
+}
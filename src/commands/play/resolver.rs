@@ -0,0 +1,306 @@
+use std::{
+    fmt,
+    sync::{Arc, LazyLock, RwLock},
+    time::Duration,
+};
+
+use log::{trace, warn};
+use reqwest::Url;
+use serenity::async_trait;
+use songbird::input::Restartable;
+
+use super::{
+    bandcamp::BandcampResolver,
+    matcher,
+    rustube::RustubeResolver,
+    services::{AppleMusicResolver, DeezerResolver, TidalResolver},
+    soundcloud::SoundcloudResolver,
+    spotify::{self, SpotifyResolver},
+    youtube::YtDlpResolver,
+};
+use crate::config::{self, Backend};
+
+pub static REGISTRY: LazyLock<Registry> = LazyLock::new(|| {
+    let mut registry = Registry::new();
+    registry.register(SpotifyResolver);
+    registry.register(SoundcloudResolver);
+    registry.register(BandcampResolver);
+    registry.register(DeezerResolver);
+    registry.register(AppleMusicResolver);
+    registry.register(TidalResolver);
+    registry.configure_youtube(&config::get().youtube.backends);
+    registry
+});
+
+fn youtube(backends: &[Backend]) -> Vec<Arc<dyn Resolver>> {
+    backends
+        .iter()
+        .map(|backend| -> Arc<dyn Resolver> {
+            match backend {
+                Backend::Rustube => Arc::new(RustubeResolver),
+                Backend::YtDlp => Arc::new(YtDlpResolver),
+            }
+        })
+        .collect()
+}
+
+pub enum Resolved {
+    Track(Restartable),
+    Playlist(Playlist),
+}
+
+pub struct Playlist {
+    pub entries: Vec<Entry>,
+    /// Entries left out of the playlist, counted by the reason
+    pub skipped: Vec<(Skip, usize)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Skip {
+    LocalFiles,
+    Episodes,
+    Unavailable,
+    Unreleased,
+}
+
+impl Skip {
+    /// Translation key of the reason, filled with the `count` of skipped entries.
+    pub fn key(self) -> &'static str {
+        match self {
+            Skip::LocalFiles => "play.skipped.local_files",
+            Skip::Episodes => "play.skipped.episodes",
+            Skip::Unavailable => "play.skipped.unavailable",
+            Skip::Unreleased => "play.skipped.unreleased",
+        }
+    }
+}
+
+pub enum Entry {
+    /// Url or search query handled by the registry again
+    Query(String),
+    /// Track known only by its metadata, which has to be matched to a playable source
+    Track(TrackInfo),
+}
+
+impl From<String> for Entry {
+    fn from(query: String) -> Self {
+        Entry::Query(query)
+    }
+}
+
+pub struct TrackInfo {
+    pub title: String,
+    pub artist: String,
+    pub duration: Option<Duration>,
+    pub isrc: Option<String>,
+}
+
+impl TrackInfo {
+    pub fn query(&self) -> String {
+        format!("{} - {}", self.title, self.artist)
+    }
+}
+
+pub fn entries(queries: Vec<String>) -> Vec<Entry> {
+    queries.into_iter().map(Entry::from).collect()
+}
+
+#[derive(Debug)]
+pub enum Error {
+    NothingFound,
+    Input(songbird::input::error::Error),
+    Spotify(spotify::Error),
+}
+
+impl Error {
+    /// Translation key of the message shown to the user.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Error::NothingFound | Error::Input(_) => "error.nothing_found",
+            Error::Spotify(e) => e.key(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NothingFound | Error::Input(_) => write!(f, "Nothing found"),
+            Error::Spotify(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<songbird::input::error::Error> for Error {
+    fn from(e: songbird::input::error::Error) -> Self {
+        Error::Input(e)
+    }
+}
+
+impl From<spotify::Error> for Error {
+    fn from(e: spotify::Error) -> Self {
+        Error::Spotify(e)
+    }
+}
+
+#[async_trait]
+pub trait Resolver: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether this resolver knows how to handle given url or search query.
+    fn claims(&self, query: &str) -> bool;
+
+    async fn resolve(&self, query: &str) -> Result<Resolved, Error>;
+}
+
+pub struct Registry {
+    resolvers: Vec<Arc<dyn Resolver>>,
+    /// Tried after every other resolver, swapped when the config changes.
+    youtube: RwLock<Vec<Arc<dyn Resolver>>>,
+}
+
+impl Registry {
+    fn new() -> Self {
+        Registry {
+            resolvers: Vec::new(),
+            youtube: RwLock::new(Vec::new()),
+        }
+    }
+
+    fn register(&mut self, resolver: impl Resolver + 'static) {
+        self.resolvers.push(Arc::new(resolver));
+    }
+
+    /// Replaces youtube backends, queries already being resolved finish with the old ones.
+    pub fn configure_youtube(&self, backends: &[Backend]) {
+        *self.youtube.write().unwrap() = youtube(backends);
+    }
+
+    /// Asks every resolver claiming `query` in priority order, returning the first success.
+    pub async fn resolve(&self, query: &str) -> Result<Resolved, Error> {
+        let claiming: Vec<Arc<dyn Resolver>> = self
+            .resolvers
+            .iter()
+            .chain(self.youtube.read().unwrap().iter())
+            .filter(|r| r.claims(query))
+            .cloned()
+            .collect();
+        let mut error = Error::NothingFound;
+        for resolver in claiming {
+            match resolver.resolve(query).await {
+                Ok(resolved) => {
+                    trace!("{} resolved {query}", resolver.name());
+                    return Ok(resolved);
+                }
+                Err(e) => {
+                    warn!("{} cannot resolve {query}: {e:?}", resolver.name());
+                    // Keep the error of the most specific resolver
+                    if matches!(error, Error::NothingFound) {
+                        error = e;
+                    }
+                }
+            }
+        }
+        Err(error)
+    }
+
+    pub async fn resolve_entry(&self, entry: &Entry) -> Result<Restartable, Error> {
+        match entry {
+            Entry::Query(query) => self.resolve_track(query).await,
+            Entry::Track(info) => {
+                trace!(
+                    "Matching {} ({:?}, isrc {:?})",
+                    info.query(),
+                    info.duration,
+                    info.isrc
+                );
+                match matcher::best_match(info).await {
+                    Some(url) => self.resolve_track(&url).await,
+                    None => self.resolve_track(&info.query()).await,
+                }
+            }
+        }
+    }
+
+    pub async fn resolve_track(&self, query: &str) -> Result<Restartable, Error> {
+        match self.resolve(query).await? {
+            Resolved::Track(source) => Ok(source),
+            Resolved::Playlist(_) => Err(Error::NothingFound),
+        }
+    }
+}
+
+pub fn is_url(query: &str) -> bool {
+    query.starts_with("https://") || query.starts_with("http://")
+}
+
+pub fn host_matches(query: &str, hosts: &[&str]) -> bool {
+    Url::parse(query)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.trim_start_matches("www.").to_string()))
+        .map(|host| hosts.iter().any(|h| *h == host))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_urls() {
+        assert!(is_url("https://open.spotify.com/playlist/abc"));
+        assert!(is_url("http://example.com"));
+        assert!(!is_url("never gonna give you up"));
+        assert!(!is_url("ftp://example.com/song.mp3"));
+    }
+
+    #[test]
+    fn matches_hosts_without_www() {
+        assert!(host_matches("https://www.deezer.com/track/1", &["deezer.com"]));
+        assert!(host_matches("https://deezer.com/track/1", &["deezer.com"]));
+        assert!(!host_matches("https://notdeezer.com/track/1", &["deezer.com"]));
+        assert!(!host_matches("deezer.com/track/1", &["deezer.com"]));
+        assert!(!host_matches("deezer", &["deezer.com"]));
+    }
+
+    #[test]
+    fn resolvers_claim_their_urls() {
+        assert!(SpotifyResolver.claims("https://open.spotify.com/playlist/37i9dQZF1DXcBWIGoYBM5M"));
+        assert!(!SpotifyResolver.claims("https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC"));
+        assert!(SoundcloudResolver.claims("https://soundcloud.com/artist/song"));
+        assert!(SoundcloudResolver.claims("https://on.soundcloud.com/abc"));
+        assert!(BandcampResolver.claims("https://artist.bandcamp.com/album/name"));
+        assert!(!BandcampResolver.claims("https://bandcamp.com.example.org/album/name"));
+        assert!(DeezerResolver.claims("https://deezer.page.link/abc"));
+        assert!(AppleMusicResolver.claims("https://music.apple.com/us/album/name/1"));
+        assert!(TidalResolver.claims("https://listen.tidal.com/track/1"));
+    }
+
+    #[test]
+    fn resolvers_ignore_other_queries() {
+        let resolvers: [&dyn Resolver; 6] = [
+            &SpotifyResolver,
+            &SoundcloudResolver,
+            &BandcampResolver,
+            &DeezerResolver,
+            &AppleMusicResolver,
+            &TidalResolver,
+        ];
+        for resolver in resolvers {
+            assert!(!resolver.claims("song name"), "{} claims a search", resolver.name());
+            assert!(
+                !resolver.claims("https://www.youtube.com/watch?v=dQw4w9WgXcQ"),
+                "{} claims a youtube url",
+                resolver.name()
+            );
+        }
+    }
+
+    #[test]
+    fn youtube_backends_take_searches() {
+        assert!(RustubeResolver.claims("song name"));
+        assert!(RustubeResolver.claims("https://youtu.be/dQw4w9WgXcQ"));
+        assert!(!RustubeResolver.claims("https://soundcloud.com/artist/song"));
+        assert!(YtDlpResolver.claims("https://example.com/stream.mp3"));
+    }
+}
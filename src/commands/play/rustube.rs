@@ -1,10 +1,45 @@
-use std::{time::Duration, process::Stdio};
+use std::time::Duration;
 
 use reqwest::Url;
-pub use rustube::get_playlist;
+use rustube::get_playlist;
 use serenity::async_trait;
-use songbird::input::{Restartable, restartable::Restart, Metadata, Codec, Container, error::Result, Input, children_to_reader};
-use std::process::Command;
+use songbird::input::{Restartable, restartable::Restart, Metadata, Codec, Container, error::{Error, Result}, Input};
+
+use super::{
+    resolver::{self, host_matches, is_url, Resolved, Resolver},
+    stream,
+};
+
+pub struct RustubeResolver;
+
+#[async_trait]
+impl Resolver for RustubeResolver {
+    fn name(&self) -> &'static str {
+        "rustube"
+    }
+
+    fn claims(&self, query: &str) -> bool {
+        !is_url(query)
+            || host_matches(query, &["youtube.com", "m.youtube.com", "music.youtube.com", "youtu.be"])
+    }
+
+    async fn resolve(&self, query: &str) -> std::result::Result<Resolved, resolver::Error> {
+        if !is_url(query) {
+            return Ok(Resolved::Track(rustube_search(query.to_string(), true).await?));
+        }
+        if query.contains("playlist") {
+            let links = get_playlist(query).await.links;
+            if links.is_empty() {
+                return Err(resolver::Error::NothingFound);
+            }
+            return Ok(Resolved::Playlist(resolver::Playlist {
+                entries: resolver::entries(links),
+                skipped: Vec::new(),
+            }));
+        }
+        Ok(Resolved::Track(rustube(query.to_string(), true).await?))
+    }
+}
 
 struct RustubeRestarter<P> 
 where
@@ -39,10 +74,10 @@ pub async fn rustube<P: AsRef<str> + Send + Clone + Sync + 'static>(uri: P, lazy
 }
 
 async fn rustube_metadata(uri: &str) -> Result<Metadata> {
-    let url = Url::parse(uri).unwrap();
+    let url = Url::parse(uri).map_err(|_| Error::Metadata)?;
     let video = match rustube::Video::from_url(&url).await {
         Ok(v) => v,
-        Err(_) => return Err(songbird::input::error::Error::Metadata),
+        Err(_) => return Err(Error::Metadata),
     };
     let video_details = video.video_details().clone();
     Ok(Metadata {
@@ -56,7 +91,7 @@ async fn rustube_metadata(uri: &str) -> Result<Metadata> {
         sample_rate: None,
         source_url: Some(uri.to_string()),
         title: Some(video_details.title.to_owned()),
-        thumbnail: Some(video_details.thumbnails.last().unwrap().url.to_owned())
+        thumbnail: video_details.thumbnails.last().map(|t| t.url.to_owned())
     })
 }
 
@@ -66,12 +101,12 @@ pub async fn rustyt(uri: impl AsRef<str>) -> Result<Input> {
 }
 
 async fn _rustyt(uri: &str, pre_args: &[&str]) -> Result<Input> {
-    let url = Url::parse(uri).unwrap();
+    let url = Url::parse(uri).map_err(|_| Error::Metadata)?;
     let video = match rustube::Video::from_url(&url).await {
         Ok(v) => v,
-        Err(_) => return Err(songbird::input::error::Error::Metadata)
+        Err(_) => return Err(Error::Metadata)
     };
-    let video_url = video.best_audio().unwrap().signature_cipher.url.as_str();
+    let video_url = video.best_audio().ok_or(Error::Metadata)?.signature_cipher.url.as_str();
     let video_details = video.video_details().clone();
     let metadata = Metadata {
         track: None,
@@ -84,41 +119,9 @@ async fn _rustyt(uri: &str, pre_args: &[&str]) -> Result<Input> {
         sample_rate: None,
         source_url: Some(uri.to_string()),
         title: Some(video_details.title.to_owned()),
-        thumbnail: Some(video_details.thumbnails.last().unwrap().url.to_owned())
+        thumbnail: video_details.thumbnails.last().map(|t| t.url.to_owned())
     };
-    let ffmpeg_args = [
-        "-f",
-        "s16le",
-        "-ac",
-        "2",
-        "-ar",
-        "48000",
-        "-acodec",
-        "pcm_f32le",
-        "-",
-    ];
-    let mut curl = Command::new("curl")
-        .arg(video_url)
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()?;
-    let ffmpeg = Command::new("ffmpeg")
-        .args(pre_args)
-        .arg("-i")
-        .arg("-")
-        .args(&ffmpeg_args)
-        .stdin(curl.stdout.take().unwrap())
-        .stderr(Stdio::null())
-        .stdout(Stdio::piped())
-        .spawn()?;
-    Ok(Input::new(
-            true,
-            children_to_reader::<f32>(vec![curl, ffmpeg]),
-            Codec::FloatPcm,
-            Container::Raw,
-            Some(metadata)
-    ))
+    stream::http(video_url, pre_args, metadata)
 }
 
 struct RustubeSearchRestarter<P> 
@@ -156,7 +159,7 @@ pub async fn rustube_search<P: AsRef<str> + Send + Clone + Sync + 'static>(uri:
 async fn rustube_search_metadata(uri: &str) -> Result<Metadata> {
     let video = match rustube::get_by_name(uri).await {
         Ok(v) => v,
-        Err(_) => return Err(songbird::input::error::Error::Metadata),
+        Err(_) => return Err(Error::Metadata),
     };
     let video_details = video.video_details().clone();
     Ok(Metadata {
@@ -170,21 +173,21 @@ async fn rustube_search_metadata(uri: &str) -> Result<Metadata> {
         sample_rate: None,
         source_url: Some(uri.to_string()),
         title: Some(video_details.title.to_owned()),
-        thumbnail: Some(video_details.thumbnails.last().unwrap().url.to_owned())
+        thumbnail: video_details.thumbnails.last().map(|t| t.url.to_owned())
     })
 }
 
 
 pub async fn rustyt_search(uri: impl AsRef<str>) -> Result<Input> {
-    _rustyt(uri.as_ref(), &[]).await
+    _rustyt_search(uri.as_ref(), &[]).await
 }
 
 async fn _rustyt_search(uri: &str, pre_args: &[&str]) -> Result<Input> {
     let video = match rustube::get_by_name(uri).await {
         Ok(v) => v,
-        Err(_) => return Err(songbird::input::error::Error::Metadata)
+        Err(_) => return Err(Error::Metadata)
     };
-    let video_url = video.best_audio().unwrap().signature_cipher.url.as_str();
+    let video_url = video.best_audio().ok_or(Error::Metadata)?.signature_cipher.url.as_str();
     let video_details = video.video_details().clone();
     let metadata = Metadata {
         track: None,
@@ -197,39 +200,16 @@ async fn _rustyt_search(uri: &str, pre_args: &[&str]) -> Result<Input> {
         sample_rate: None,
         source_url: Some(uri.to_string()),
         title: Some(video_details.title.to_owned()),
-        thumbnail: Some(video_details.thumbnails.last().unwrap().url.to_owned())
+        thumbnail: video_details.thumbnails.last().map(|t| t.url.to_owned())
     };
-    let ffmpeg_args = [
-        "-f",
-        "s16le",
-        "-ac",
-        "2",
-        "-ar",
-        "48000",
-        "-acodec",
-        "pcm_f32le",
-        "-",
-    ];
-    let mut curl = Command::new("curl")
-        .arg(video_url)
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()?;
-    let ffmpeg = Command::new("ffmpeg")
-        .args(pre_args)
-        .arg("-i")
-        .arg("-")
-        .args(&ffmpeg_args)
-        .stdin(curl.stdout.take().unwrap())
-        .stderr(Stdio::null())
-        .stdout(Stdio::piped())
-        .spawn()?;
-    Ok(Input::new(
-            true,
-            children_to_reader::<f32>(vec![curl, ffmpeg]),
-            Codec::FloatPcm,
-            Container::Raw,
-            Some(metadata)
-    ))
+    stream::http(video_url, pre_args, metadata)
+}
+
+/// Fetches fresh stream of `uri`, a yt link or search query, starting at `position`.
+pub async fn resume(uri: &str, position: Duration) -> Result<Input> {
+    if is_url(uri) {
+        RustubeRestarter { uri }.call_restart(Some(position)).await
+    } else {
+        RustubeSearchRestarter { uri }.call_restart(Some(position)).await
+    }
 }
@@ -0,0 +1,302 @@
+use std::time::Duration;
+
+use reqwest::Url;
+use serde_json::Value;
+use serenity::async_trait;
+
+use super::{
+    resolver::{self, host_matches, Entry, Resolved, Resolver, TrackInfo, REGISTRY},
+    CLIENT,
+};
+
+
+async fn get_json(url: &str) -> Option<Value> {
+    let response = CLIENT.get(url).send().await.ok()?.error_for_status().ok()?;
+    serde_json::from_str(&response.text().await.ok()?).ok()
+}
+
+/// Follows redirects of short share links, returning the final url.
+async fn expand(query: &str) -> Option<Url> {
+    let response = CLIENT.get(query).send().await.ok()?;
+    Some(response.url().clone())
+}
+
+/// Returns `content` of every `<meta>` tag with given `property` or `name`.
+fn meta(page: &str, property: &str) -> Vec<String> {
+    page.split("<meta ")
+        .skip(1)
+        .filter_map(|tag| {
+            let tag = tag.split('>').next()?;
+            let matches = attr(tag, "property").or_else(|| attr(tag, "name"))? == property;
+            matches.then(|| attr(tag, "content")).flatten()
+        })
+        .map(|content| {
+            content
+                .replace("&quot;", "\"")
+                .replace("&#39;", "'")
+                .replace("&amp;", "&")
+        })
+        .collect()
+}
+
+fn attr<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    tag.split(&format!(" {name}=\""))
+        .nth(1)
+        .or_else(|| tag.strip_prefix(&format!("{name}=\"")))
+        .and_then(|value| value.split('"').next())
+}
+
+/// Last two path segments, like `track` and `123` of `/en/track/123`.
+fn kind_and_id(url: &Url) -> Option<(&str, &str)> {
+    let mut segments = url.path_segments()?.rev().filter(|segment| !segment.is_empty());
+    let id = segments.next()?;
+    let kind = segments.next()?;
+    Some((kind, id))
+}
+
+/// Single tracks are matched right away, collections are queued entry by entry.
+async fn resolved(mut tracks: Vec<Entry>) -> Result<Resolved, resolver::Error> {
+    match tracks.len() {
+        0 => Err(resolver::Error::NothingFound),
+        1 => Ok(Resolved::Track(REGISTRY.resolve_entry(&tracks.remove(0)).await?)),
+        _ => Ok(Resolved::Playlist(resolver::Playlist {
+            entries: tracks,
+            skipped: Vec::new(),
+        })),
+    }
+}
+
+fn deezer_track(track: &Value) -> Option<TrackInfo> {
+    Some(TrackInfo {
+        title: track.get("title").and_then(Value::as_str)?.to_string(),
+        artist: track
+            .get("artist")
+            .and_then(|a| a.get("name"))
+            .and_then(Value::as_str)?
+            .to_string(),
+        duration: track.get("duration").and_then(Value::as_u64).map(Duration::from_secs),
+        isrc: track.get("isrc").and_then(Value::as_str).map(str::to_string),
+    })
+}
+
+pub struct DeezerResolver;
+
+#[async_trait]
+impl Resolver for DeezerResolver {
+    fn name(&self) -> &'static str {
+        "deezer"
+    }
+
+    fn claims(&self, query: &str) -> bool {
+        host_matches(query, &["deezer.com", "deezer.page.link", "link.deezer.com"])
+    }
+
+    async fn resolve(&self, query: &str) -> Result<Resolved, resolver::Error> {
+        let url = expand(query).await.ok_or(resolver::Error::NothingFound)?;
+        // Paths look like /en/track/123, language part is optional
+        let (kind, id) = kind_and_id(&url).ok_or(resolver::Error::NothingFound)?;
+
+        let tracks = match kind {
+            "track" => get_json(&format!("https://api.deezer.com/track/{id}"))
+                .await
+                .as_ref()
+                .and_then(deezer_track)
+                .map(Entry::Track)
+                .into_iter()
+                .collect(),
+            "album" | "playlist" => {
+                let mut tracks = Vec::new();
+                let mut next = Some(format!("https://api.deezer.com/{kind}/{id}/tracks?limit=100"));
+                while let Some(url) = next {
+                    let page = get_json(&url).await.ok_or(resolver::Error::NothingFound)?;
+                    if let Some(data) = page.get("data").and_then(Value::as_array) {
+                        tracks.extend(data.iter().filter_map(deezer_track).map(Entry::Track));
+                    }
+                    next = page.get("next").and_then(Value::as_str).map(str::to_string);
+                }
+                tracks
+            }
+            _ => Vec::new(),
+        };
+        resolved(tracks).await
+    }
+}
+
+pub struct AppleMusicResolver;
+
+#[async_trait]
+impl Resolver for AppleMusicResolver {
+    fn name(&self) -> &'static str {
+        "apple music"
+    }
+
+    fn claims(&self, query: &str) -> bool {
+        host_matches(query, &["music.apple.com"])
+    }
+
+    async fn resolve(&self, query: &str) -> Result<Resolved, resolver::Error> {
+        let url = Url::parse(query).map_err(|_| resolver::Error::NothingFound)?;
+        let segments: Vec<&str> = url.path_segments().map(|s| s.collect()).unwrap_or_default();
+        let country = segments.first().copied().unwrap_or("us");
+        // Album links pointing at a single song carry its id in `i`
+        let song = url
+            .query_pairs()
+            .find(|(key, _)| key == "i")
+            .map(|(_, id)| id.to_string());
+
+        let id = match (segments.get(1).copied(), song) {
+            (Some("album"), Some(song)) | (Some("song"), Some(song)) => song,
+            (Some("song"), None) | (Some("album"), None) => {
+                segments.last().copied().unwrap_or_default().to_string()
+            }
+            _ => {
+                // Playlists are not in the lookup api, but their pages list songs
+                let page = match CLIENT.get(query).send().await {
+                    Ok(response) => response.text().await.unwrap_or_default(),
+                    Err(_) => return Err(resolver::Error::NothingFound),
+                };
+                let songs: Vec<Entry> = meta(&page, "music:song").into_iter().map(Entry::Query).collect();
+                return resolved(songs).await;
+            }
+        };
+
+        let lookup = get_json(&format!(
+            "https://itunes.apple.com/lookup?id={id}&entity=song&country={country}"
+        ))
+        .await
+        .ok_or(resolver::Error::NothingFound)?;
+        let tracks = lookup
+            .get("results")
+            .and_then(Value::as_array)
+            .map(|results| {
+                results
+                    .iter()
+                    .filter(|r| r.get("wrapperType").and_then(Value::as_str) == Some("track"))
+                    .filter_map(|r| {
+                        Some(Entry::Track(TrackInfo {
+                            title: r.get("trackName").and_then(Value::as_str)?.to_string(),
+                            artist: r.get("artistName").and_then(Value::as_str)?.to_string(),
+                            duration: r
+                                .get("trackTimeMillis")
+                                .and_then(Value::as_u64)
+                                .map(Duration::from_millis),
+                            isrc: None,
+                        }))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        resolved(tracks).await
+    }
+}
+
+pub struct TidalResolver;
+
+#[async_trait]
+impl Resolver for TidalResolver {
+    fn name(&self) -> &'static str {
+        "tidal"
+    }
+
+    fn claims(&self, query: &str) -> bool {
+        host_matches(query, &["tidal.com", "listen.tidal.com"])
+    }
+
+    async fn resolve(&self, query: &str) -> Result<Resolved, resolver::Error> {
+        let url = Url::parse(query).map_err(|_| resolver::Error::NothingFound)?;
+        let (kind, id) = kind_and_id(&url).ok_or(resolver::Error::NothingFound)?;
+        // Public pages only render on the browse domain
+        let page_url = format!("https://tidal.com/browse/{kind}/{id}");
+        let page = match CLIENT.get(&page_url).send().await {
+            Ok(response) => response.text().await.unwrap_or_default(),
+            Err(_) => return Err(resolver::Error::NothingFound),
+        };
+
+        if kind != "track" {
+            let songs: Vec<Entry> = meta(&page, "music:song").into_iter().map(Entry::Query).collect();
+            return resolved(songs).await;
+        }
+
+        let oembed = match Url::parse_with_params("https://oembed.tidal.com/", &[("url", &page_url)]) {
+            Ok(url) => get_json(url.as_str()).await,
+            Err(_) => None,
+        };
+        let title = oembed
+            .as_ref()
+            .and_then(|o| o.get("title"))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .or_else(|| meta(&page, "og:title").into_iter().next());
+        let artist = oembed
+            .as_ref()
+            .and_then(|o| o.get("author_name"))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .or_else(|| meta(&page, "music:musician:name").into_iter().next());
+        let (title, artist) = match (title, artist) {
+            (Some(title), Some(artist)) => (title, artist),
+            // Without artist the page title alone is still a decent search query
+            (Some(title), None) => return resolved(vec![Entry::Query(title)]).await,
+            _ => return Err(resolver::Error::NothingFound),
+        };
+
+        resolved(vec![Entry::Track(TrackInfo {
+            title,
+            artist,
+            duration: meta(&page, "music:duration")
+                .first()
+                .and_then(|d| d.parse().ok())
+                .map(Duration::from_secs),
+            isrc: meta(&page, "music:isrc").into_iter().next(),
+        })])
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn splits_kind_and_id() {
+        assert_eq!(kind_and_id(&url("https://www.deezer.com/en/track/3135556")), Some(("track", "3135556")));
+        assert_eq!(kind_and_id(&url("https://www.deezer.com/album/302127")), Some(("album", "302127")));
+        assert_eq!(kind_and_id(&url("https://listen.tidal.com/track/77646168/")), Some(("track", "77646168")));
+        assert_eq!(kind_and_id(&url("https://tidal.com/1")), None);
+    }
+
+    #[test]
+    fn reads_meta_tags() {
+        let page = r#"<head>
+            <meta property="og:title" content="Song &amp; &quot;Remix&quot;">
+            <meta name="music:song" content="https://music.apple.com/us/song/1" />
+            <meta property="music:song" content="https://music.apple.com/us/song/2">
+            <meta property="og:description" content="Other">
+        </head>"#;
+        assert_eq!(meta(page, "og:title"), ["Song & \"Remix\""]);
+        assert_eq!(
+            meta(page, "music:song"),
+            ["https://music.apple.com/us/song/1", "https://music.apple.com/us/song/2"]
+        );
+        assert!(meta(page, "music:isrc").is_empty());
+    }
+
+    #[test]
+    fn reads_deezer_tracks() {
+        let track = serde_json::json!({
+            "title": "Harder, Better, Faster, Stronger",
+            "artist": { "name": "Daft Punk" },
+            "duration": 224,
+            "isrc": "GBDUW0000059",
+        });
+        let info = deezer_track(&track).unwrap();
+        assert_eq!(info.query(), "Harder, Better, Faster, Stronger - Daft Punk");
+        assert_eq!(info.duration, Some(Duration::from_secs(224)));
+        assert_eq!(info.isrc.as_deref(), Some("GBDUW0000059"));
+        assert!(deezer_track(&serde_json::json!({ "title": "No artist" })).is_none());
+    }
+}
@@ -0,0 +1,249 @@
+use std::time::Duration;
+
+use log::{info, warn};
+use rusqlite::{params, Connection};
+use serenity::{
+    model::prelude::{ChannelId, GuildId, UserId},
+    prelude::Context,
+};
+use songbird::{
+    tracks::{LoopState, TrackHandle},
+    Songbird,
+};
+
+use super::{autoplay, player, resolver::REGISTRY, Queued};
+use crate::{commands::manager, db, errors::Result, locale::Lang, settings};
+
+/// What was playing in a guild when the bot shut down.
+struct Session {
+    channel_id: ChannelId,
+    /// Position in the first entry.
+    position: Duration,
+    looping: bool,
+    volume: f32,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    url: String,
+    /// Only for the logs, restored tracks get fresh metadata from their source.
+    title: Option<String>,
+    requester: Option<UserId>,
+    announce: ChannelId,
+}
+
+async fn snapshot(channel_id: ChannelId, queue: &[TrackHandle]) -> Option<Session> {
+    let current = queue.first()?;
+    let state = current.get_info().await.ok();
+    let offset = Queued::of(current).await.map_or(Duration::ZERO, |queued| queued.offset);
+
+    let mut entries = Vec::new();
+    let mut current_saved = false;
+    for (index, track) in queue.iter().enumerate() {
+        let metadata = track.metadata();
+        // Without the url there is nothing to queue again
+        let (url, queued) = match (metadata.source_url.clone(), Queued::of(track).await) {
+            (Some(url), Some(queued)) => (url, queued),
+            _ => continue,
+        };
+        current_saved |= index == 0;
+        entries.push(Entry {
+            url,
+            title: metadata.title.clone(),
+            requester: queued.requester,
+            announce: queued.announce,
+        });
+    }
+    if entries.is_empty() {
+        return None;
+    }
+
+    // Position and looping belong to the current track, not to whichever one got saved first
+    let current_state = state.as_ref().filter(|_| current_saved);
+    Some(Session {
+        channel_id,
+        position: current_state.map_or(Duration::ZERO, |state| offset + state.position),
+        looping: current_state.map_or(false, |state| matches!(state.loops, LoopState::Infinite)),
+        volume: state.map_or(1.0, |state| state.volume),
+        entries,
+    })
+}
+
+fn store(db: &mut Connection, guild_id: GuildId, session: &Session) -> rusqlite::Result<()> {
+    let transaction = db.transaction()?;
+    transaction.execute("DELETE FROM session_tracks WHERE guild_id = ?1", params![guild_id.0])?;
+    transaction.execute(
+        "INSERT OR REPLACE INTO sessions (guild_id, channel_id, position, looping, volume) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            guild_id.0,
+            session.channel_id.0,
+            session.position.as_millis() as u64,
+            session.looping,
+            session.volume,
+        ],
+    )?;
+    for (ordinal, entry) in session.entries.iter().enumerate() {
+        transaction.execute(
+            "INSERT INTO session_tracks
+             (guild_id, ordinal, url, title, requester, announce_channel)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                guild_id.0,
+                ordinal,
+                entry.url,
+                entry.title,
+                entry.requester.map(|user| user.0),
+                entry.announce.0,
+            ],
+        )?;
+    }
+    transaction.commit()
+}
+
+/// Saves what plays in `guilds` and leaves their voice channels.
+pub async fn save(manager: &Songbird, guilds: Vec<GuildId>) {
+    for guild_id in guilds {
+        let handler_lock = match manager.get(guild_id) {
+            Some(handler_lock) => handler_lock,
+            None => continue,
+        };
+        let mut handler = handler_lock.lock().await;
+        let session = match handler.current_channel() {
+            Some(channel_id) => snapshot(ChannelId(channel_id.0), &handler.queue().current_queue()).await,
+            None => None,
+        };
+        if let Some(session) = session {
+            let stored = db::run(move |db| store(db, guild_id, &session).map(|()| session.entries.len())).await;
+            match stored {
+                Ok(songs) => info!("Saved {songs} songs of {guild_id}"),
+                Err(e) => warn!("Cannot save session of {guild_id}: {e}"),
+            }
+        }
+
+        // Stopped tracks are left alone by stream recovery and autoplay
+        handler.queue().stop();
+        if let Err(e) = handler.leave().await {
+            warn!("Cannot leave voice channel in {guild_id}: {e}");
+        }
+    }
+}
+
+/// Discord sends events of a guild through shard `(guild_id >> 22) % shard count`.
+fn on_shard(guild_id: GuildId, [shard, shards]: [u64; 2]) -> bool {
+    (guild_id.0 >> 22) % shards == shard
+}
+
+/// Takes saved sessions of guilds on `shard` out of the database, so they are restored only once.
+fn take(db: &mut Connection, shard: [u64; 2]) -> rusqlite::Result<Vec<(GuildId, Session)>> {
+    let transaction = db.transaction()?;
+    let mut sessions: Vec<(GuildId, Session)> = transaction
+        .prepare("SELECT guild_id, channel_id, position, looping, volume FROM sessions")?
+        .query_map([], |row| {
+            Ok((
+                GuildId(row.get(0)?),
+                Session {
+                    channel_id: ChannelId(row.get(1)?),
+                    position: Duration::from_millis(row.get(2)?),
+                    looping: row.get(3)?,
+                    volume: row.get(4)?,
+                    entries: Vec::new(),
+                },
+            ))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    sessions.retain(|(guild_id, _)| on_shard(*guild_id, shard));
+
+    {
+        let mut statement = transaction.prepare(
+            "SELECT url, title, requester, announce_channel
+             FROM session_tracks WHERE guild_id = ?1 ORDER BY ordinal",
+        )?;
+        for (guild_id, session) in &mut sessions {
+            session.entries = statement
+                .query_map(params![guild_id.0], |row| {
+                    Ok(Entry {
+                        url: row.get(0)?,
+                        title: row.get(1)?,
+                        requester: row.get::<_, Option<u64>>(2)?.map(UserId),
+                        announce: ChannelId(row.get(3)?),
+                    })
+                })?
+                .collect::<rusqlite::Result<_>>()?;
+        }
+    }
+
+    for (guild_id, _) in &sessions {
+        transaction.execute("DELETE FROM sessions WHERE guild_id = ?1", params![guild_id.0])?;
+        transaction.execute("DELETE FROM session_tracks WHERE guild_id = ?1", params![guild_id.0])?;
+    }
+    transaction.commit()?;
+    Ok(sessions)
+}
+
+async fn resume(ctx: &Context, guild_id: GuildId, session: Session) -> Result<()> {
+    let manager = manager(ctx).await?;
+    let settings = settings::get(guild_id);
+    let handler_lock = manager.get_or_insert(guild_id);
+    handler_lock.lock().await.join(session.channel_id).await?;
+
+    let mut restored = 0;
+    for (index, entry) in session.entries.into_iter().enumerate() {
+        let title = entry.title.as_deref().unwrap_or(&entry.url);
+        let source = match REGISTRY.resolve_track(&entry.url).await {
+            Ok(source) => source,
+            Err(e) => {
+                warn!("Cannot restore {title} in {guild_id}: {e:?}");
+                continue;
+            }
+        };
+        let queued = Queued {
+            requester: entry.requester,
+            announce: entry.announce,
+            offset: Duration::ZERO,
+            lang: Lang::for_guild(guild_id),
+        };
+        let (mut track, track_handle) = player(ctx, &manager, guild_id, &settings, queued, source).await?;
+        track.set_volume(session.volume);
+
+        let mut handler = handler_lock.lock().await;
+        autoplay::watch(
+            &handler_lock,
+            &mut handler,
+            guild_id,
+            ctx.http.clone(),
+            manager.clone(),
+            ctx.cache.current_user_id(),
+        );
+        handler.enqueue(track);
+        if index == 0 {
+            if !session.position.is_zero() {
+                track_handle.seek_time(session.position)?;
+            }
+            if session.looping {
+                track_handle.enable_loop()?;
+            }
+        }
+        restored += 1;
+    }
+    info!("Restored {restored} songs in {guild_id}");
+    Ok(())
+}
+
+/// Rejoins voice channels saved on shutdown in guilds of `shard` and picks up where playback stopped.
+pub async fn restore(ctx: Context, shard: [u64; 2]) {
+    let sessions = match db::run(move |db| take(db, shard)).await {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            warn!("Cannot read saved sessions: {e}");
+            return;
+        }
+    };
+    for (guild_id, session) in sessions {
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = resume(&ctx, guild_id, session).await {
+                warn!("Cannot restore session of {guild_id}: {e:?}");
+            }
+        });
+    }
+}
@@ -0,0 +1,274 @@
+use std::{sync::LazyLock, time::Duration};
+
+use log::{info, warn};
+use reqwest::Url;
+use serde_json::Value;
+use serenity::async_trait;
+use songbird::input::{
+    error::{Error, Result},
+    restartable::Restart,
+    Codec, Container, Input, Metadata, Restartable,
+};
+use tokio::sync::Mutex;
+
+use super::{
+    resolver::{self, host_matches, Resolved, Resolver},
+    stream, CLIENT,
+};
+
+const API: &str = "https://api-v2.soundcloud.com";
+const MAX_LIKES: usize = 500;
+
+static CLIENT_ID: LazyLock<Mutex<Option<String>>> = LazyLock::new(|| Mutex::new(None));
+
+/// SoundCloud has no public api keys anymore, the web player one is scraped from its scripts.
+async fn client_id() -> Option<String> {
+    let mut client_id = CLIENT_ID.lock().await;
+    if let Some(id) = client_id.as_ref() {
+        return Some(id.clone());
+    }
+
+    let page = CLIENT.get("https://soundcloud.com").send().await.ok()?.text().await.ok()?;
+    let scripts: Vec<&str> = page
+        .split("<script crossorigin src=\"")
+        .skip(1)
+        .filter_map(|s| s.split('"').next())
+        .collect();
+    // The key lives in one of the last bundles
+    for script in scripts.into_iter().rev() {
+        let body = match CLIENT.get(script).send().await {
+            Ok(response) => response.text().await.unwrap_or_default(),
+            Err(_) => continue,
+        };
+        if let Some(id) = body
+            .split("client_id:\"")
+            .nth(1)
+            .and_then(|s| s.split('"').next())
+        {
+            info!("Found SoundCloud client id");
+            *client_id = Some(id.to_string());
+            return client_id.clone();
+        }
+    }
+    warn!("Cannot find SoundCloud client id");
+    None
+}
+
+async fn api(url: &str) -> Option<Value> {
+    for _ in 0..2 {
+        let id = client_id().await?;
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let response = CLIENT
+            .get(format!("{url}{separator}client_id={id}"))
+            .send()
+            .await
+            .ok()?;
+        match response.status().as_u16() {
+            // Scraped key got rotated, find a new one
+            401 | 403 => *CLIENT_ID.lock().await = None,
+            _ => return serde_json::from_str(&response.error_for_status().ok()?.text().await.ok()?).ok(),
+        }
+    }
+    None
+}
+
+fn metadata(track: &Value) -> Metadata {
+    let artist = track
+        .get("publisher_metadata")
+        .and_then(|p| p.get("artist"))
+        .and_then(Value::as_str)
+        .or_else(|| track.get("user").and_then(|u| u.get("username")).and_then(Value::as_str));
+    let artwork = track
+        .get("artwork_url")
+        .and_then(Value::as_str)
+        .or_else(|| track.get("user").and_then(|u| u.get("avatar_url")).and_then(Value::as_str))
+        .map(|url| url.replace("-large", "-t500x500"));
+    Metadata {
+        track: None,
+        artist: artist.map(str::to_string),
+        date: track.get("display_date").and_then(Value::as_str).map(str::to_string),
+        channels: Some(2),
+        channel: track
+            .get("user")
+            .and_then(|u| u.get("username"))
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        start_time: None,
+        duration: track
+            .get("duration")
+            .and_then(Value::as_u64)
+            .map(Duration::from_millis),
+        sample_rate: None,
+        source_url: track.get("permalink_url").and_then(Value::as_str).map(str::to_string),
+        title: track.get("title").and_then(Value::as_str).map(str::to_string),
+        thumbnail: artwork,
+    }
+}
+
+/// Picks progressive stream when available, falling back to HLS.
+async fn stream_url(track: &Value) -> Option<String> {
+    let transcodings = track
+        .get("media")
+        .and_then(|m| m.get("transcodings"))
+        .and_then(Value::as_array)?;
+    let protocol = |t: &&Value| {
+        t.get("format")
+            .and_then(|f| f.get("protocol"))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    let transcoding = transcodings
+        .iter()
+        .find(|t| protocol(t).as_deref() == Some("progressive"))
+        .or_else(|| transcodings.iter().find(|t| protocol(t).as_deref() == Some("hls")))?;
+    let url = transcoding.get("url").and_then(Value::as_str)?;
+    let url = match track.get("track_authorization").and_then(Value::as_str) {
+        Some(auth) => format!("{url}?track_authorization={auth}"),
+        None => url.to_string(),
+    };
+    api(&url)
+        .await?
+        .get("url")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+struct SoundcloudRestarter {
+    track: Value,
+}
+
+#[async_trait]
+impl Restart for SoundcloudRestarter {
+    async fn lazy_init(&mut self) -> Result<(Option<Metadata>, Codec, Container)> {
+        Ok((Some(metadata(&self.track)), Codec::FloatPcm, Container::Raw))
+    }
+
+    async fn call_restart(&mut self, time: Option<Duration>) -> Result<Input> {
+        // Stream urls expire, so a new one is requested on every restart
+        let url = stream_url(&self.track).await.ok_or(Error::Metadata)?;
+        if let Some(time) = time {
+            let ts = format!("{:.3}", time.as_secs_f64());
+
+            stream::ffmpeg(&url, &["-ss", &ts], metadata(&self.track))
+        } else {
+            stream::ffmpeg(&url, &[], metadata(&self.track))
+        }
+    }
+}
+
+pub async fn soundcloud(track: Value, lazy: bool) -> Result<Restartable> {
+    Restartable::new(SoundcloudRestarter { track }, lazy).await
+}
+
+/// Sets only contain full objects for first few tracks, rest has to be fetched by ids.
+async fn set_tracks(set: &Value) -> Vec<String> {
+    let tracks = match set.get("tracks").and_then(Value::as_array) {
+        Some(tracks) => tracks,
+        None => return Vec::new(),
+    };
+    let missing: Vec<u64> = tracks
+        .iter()
+        .filter(|t| t.get("permalink_url").is_none())
+        .filter_map(|t| t.get("id").and_then(Value::as_u64))
+        .collect();
+
+    let mut fetched = Vec::new();
+    for ids in missing.chunks(50) {
+        let ids: Vec<String> = ids.iter().map(u64::to_string).collect();
+        if let Some(Value::Array(tracks)) = api(&format!("{API}/tracks?ids={}", ids.join(","))).await {
+            fetched.extend(tracks);
+        }
+    }
+
+    tracks
+        .iter()
+        .filter_map(|t| {
+            t.get("permalink_url").and_then(Value::as_str).map(str::to_string).or_else(|| {
+                let id = t.get("id")?;
+                fetched
+                    .iter()
+                    .find(|f| f.get("id") == Some(id))
+                    .and_then(|f| f.get("permalink_url"))
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            })
+        })
+        .collect()
+}
+
+async fn likes(user: &Value) -> Vec<String> {
+    let id = match user.get("id").and_then(Value::as_u64) {
+        Some(id) => id,
+        None => return Vec::new(),
+    };
+    let mut url = format!("{API}/users/{id}/likes?limit=200");
+    let mut links = Vec::new();
+    while links.len() < MAX_LIKES {
+        let page = match api(&url).await {
+            Some(page) => page,
+            None => break,
+        };
+        if let Some(collection) = page.get("collection").and_then(Value::as_array) {
+            links.extend(collection.iter().filter_map(|like| {
+                like.get("track")
+                    .and_then(|t| t.get("permalink_url"))
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            }));
+        }
+        match page.get("next_href").and_then(Value::as_str) {
+            Some(next) => url = next.to_string(),
+            None => break,
+        }
+    }
+    links.truncate(MAX_LIKES);
+    links
+}
+
+pub struct SoundcloudResolver;
+
+#[async_trait]
+impl Resolver for SoundcloudResolver {
+    fn name(&self) -> &'static str {
+        "soundcloud"
+    }
+
+    fn claims(&self, query: &str) -> bool {
+        host_matches(query, &["soundcloud.com", "m.soundcloud.com", "on.soundcloud.com"])
+    }
+
+    async fn resolve(&self, query: &str) -> std::result::Result<Resolved, resolver::Error> {
+        // Short links have to be expanded before api understands them
+        let mut uri = match host_matches(query, &["on.soundcloud.com"]) {
+            true => match CLIENT.get(query).send().await {
+                Ok(response) => response.url().to_string(),
+                Err(_) => return Err(resolver::Error::NothingFound),
+            },
+            false => query.to_string(),
+        };
+        uri = uri.split('?').next().unwrap_or_default().trim_end_matches('/').to_string();
+        let liked = uri.ends_with("/likes");
+        if liked {
+            uri = uri.trim_end_matches("/likes").to_string();
+        }
+
+        let resolve = Url::parse_with_params(&format!("{API}/resolve"), &[("url", &uri)])
+            .map_err(|_| resolver::Error::NothingFound)?;
+        let resolved = api(resolve.as_str())
+            .await
+            .ok_or(resolver::Error::NothingFound)?;
+        let entries = match (resolved.get("kind").and_then(Value::as_str), liked) {
+            (Some("track"), _) => return Ok(Resolved::Track(soundcloud(resolved, true).await?)),
+            (Some("playlist"), _) => set_tracks(&resolved).await,
+            (Some("user"), true) => likes(&resolved).await,
+            _ => return Err(resolver::Error::NothingFound),
+        };
+        if entries.is_empty() {
+            return Err(resolver::Error::NothingFound);
+        }
+        Ok(Resolved::Playlist(resolver::Playlist {
+            entries: resolver::entries(entries),
+            skipped: Vec::new(),
+        }))
+    }
+}
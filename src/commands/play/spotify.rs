@@ -1,65 +1,300 @@
-use crate::config;
+use std::{
+    fmt,
+    sync::{Arc, LazyLock, RwLock},
+    time::{Duration, Instant},
+};
 
-use reqwest::{header::HeaderValue, Method, Url};
-use rspotify::{ClientCredsSpotify, Credentials};
+use super::{
+    resolver::{self, host_matches, Entry, Resolved, Resolver, Skip, TrackInfo},
+    CLIENT,
+};
+use crate::config::{self, SpotifyConfig};
 
+use log::{info, warn};
+use reqwest::{header::RETRY_AFTER, StatusCode};
 use serde_json::Value;
+use serenity::async_trait;
+use tokio::sync::Mutex;
 
-async fn auth() -> String {
-    let client_id = config::SPOTIFY_CONFIG.get("client_id").unwrap().as_str().unwrap();
-    let client_secret = config::SPOTIFY_CONFIG.get("client_secret").unwrap().as_str().unwrap();
-    let credentials = Credentials::new(client_id, client_secret);
-
-    let request = ClientCredsSpotify::new(credentials);
-    request.request_token().await.unwrap();
-    request
-        .token
-        .clone()
-        .lock()
-        .await
-        .unwrap()
-        .clone()
-        .unwrap()
-        .access_token
+const MAX_RETRIES: usize = 5;
+/// Longer waits would outlast the interaction, the user is better off trying again later.
+const MAX_RETRY_AFTER: u64 = 5;
+const TOKEN_MARGIN: Duration = Duration::from_secs(60);
+
+static SPOTIFY: LazyLock<RwLock<Option<Arc<SpotifyClient>>>> =
+    LazyLock::new(|| RwLock::new(client(config::get().spotify.as_ref())));
+
+fn client(config: Option<&SpotifyConfig>) -> Option<Arc<SpotifyClient>> {
+    match config {
+        Some(spotify) if !spotify.client_id.is_empty() => Some(Arc::new(SpotifyClient::new(
+            &spotify.client_id,
+            &spotify.client_secret,
+            &spotify.market,
+        ))),
+        _ => {
+            warn!("Spotify credentials missing. Spotify links are disabled");
+            None
+        }
+    }
+}
+
+/// Swaps credentials used for new requests, ones already running finish with the old client.
+pub fn configure(config: Option<&SpotifyConfig>) {
+    *SPOTIFY.write().unwrap() = client(config);
+}
+
+#[derive(Debug)]
+pub enum Error {
+    NotConfigured,
+    Auth,
+    RateLimited,
+    Request(reqwest::Error),
+    Status(StatusCode),
+    Parse,
+}
+
+impl Error {
+    /// Translation key of the message shown to the user, details only go to the logs.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Error::NotConfigured => "error.spotify_not_configured",
+            Error::Auth => "error.spotify_auth",
+            Error::RateLimited => "error.spotify_rate_limited",
+            Error::Request(_) | Error::Status(_) | Error::Parse => "error.spotify",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotConfigured => write!(f, "Spotify is not configured"),
+            Error::Auth => write!(f, "Cannot authorize with Spotify"),
+            Error::RateLimited => write!(f, "Spotify rate limit reached, try again later"),
+            Error::Request(e) => write!(f, "Spotify request failed: {e}"),
+            Error::Status(status) => write!(f, "Spotify responded with {status}"),
+            Error::Parse => write!(f, "Cannot parse Spotify response"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Request(e)
+    }
+}
+
+struct Token {
+    access_token: String,
+    expires_at: Instant,
 }
 
-pub async fn playlist(uri: &str) -> Option<Vec<String>> {
-    let id = uri.split('/').last().unwrap();
-    let url = format!("https://api.spotify.com/v1/playlists/{id}?market=PL&fields=tracks.items(track.name%2C%20track.artists.name)");
-
-    let client = reqwest::Client::new();
-    let mut reqwest = reqwest::Request::new(Method::GET, Url::parse(&url).unwrap());
-    reqwest
-        .headers_mut()
-        .insert("Accept", HeaderValue::from_static("application/json"));
-    reqwest
-        .headers_mut()
-        .insert("Content-Type", HeaderValue::from_static("application/json"));
-    reqwest.headers_mut().insert(
-        "Authorization",
-        HeaderValue::from_str(&format!("Bearer {}", auth().await)).unwrap(),
+pub struct SpotifyClient {
+    client_id: String,
+    client_secret: String,
+    market: String,
+    token: Mutex<Option<Token>>,
+}
+
+impl SpotifyClient {
+    fn new(client_id: &str, client_secret: &str, market: &str) -> Self {
+        SpotifyClient {
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            market: market.to_string(),
+            token: Mutex::new(None),
+        }
+    }
+
+    async fn token(&self) -> Result<String, Error> {
+        let mut token = self.token.lock().await;
+        if let Some(t) = token.as_ref() {
+            if Instant::now() + TOKEN_MARGIN < t.expires_at {
+                return Ok(t.access_token.clone());
+            }
+        }
+
+        let response = CLIENT
+            .post("https://accounts.spotify.com/api/token")
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&[("grant_type", "client_credentials")])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            warn!("Spotify token request failed: {}", response.status());
+            return Err(Error::Auth);
+        }
+        let json: Value = serde_json::from_str(&response.text().await?).map_err(|_| Error::Parse)?;
+        let access_token = json
+            .get("access_token")
+            .and_then(Value::as_str)
+            .ok_or(Error::Auth)?
+            .to_string();
+        let expires_in = json.get("expires_in").and_then(Value::as_u64).unwrap_or(3600);
+        info!("Refreshed Spotify token, valid for {expires_in}s");
+
+        *token = Some(Token {
+            access_token: access_token.clone(),
+            expires_at: Instant::now() + Duration::from_secs(expires_in),
+        });
+        Ok(access_token)
+    }
+
+    async fn invalidate_token(&self) {
+        *self.token.lock().await = None;
+    }
+
+    async fn get(&self, url: &str) -> Result<Value, Error> {
+        let mut last_status = None;
+        for _ in 0..MAX_RETRIES {
+            let response = CLIENT
+                .get(url)
+                .header("Accept", "application/json")
+                .bearer_auth(self.token().await?)
+                .send()
+                .await?;
+
+            last_status = Some(response.status());
+            match response.status() {
+                StatusCode::TOO_MANY_REQUESTS => {
+                    let retry_after = response
+                        .headers()
+                        .get(RETRY_AFTER)
+                        .and_then(|h| h.to_str().ok())
+                        .and_then(|h| h.parse().ok())
+                        .unwrap_or(1);
+                    if retry_after > MAX_RETRY_AFTER {
+                        warn!("Spotify rate limit hit, asked to wait {retry_after}s");
+                        return Err(Error::RateLimited);
+                    }
+                    warn!("Spotify rate limit hit, retrying after {retry_after}s");
+                    tokio::time::sleep(Duration::from_secs(retry_after)).await;
+                }
+                StatusCode::UNAUTHORIZED => self.invalidate_token().await,
+                status if status.is_success() => {
+                    return serde_json::from_str(&response.text().await?).map_err(|_| Error::Parse)
+                }
+                status => return Err(Error::Status(status)),
+            }
+        }
+        // Fresh tokens rejected every time mean the credentials themselves are wrong
+        if last_status == Some(StatusCode::UNAUTHORIZED) {
+            warn!("Spotify rejected every token, check client_id and client_secret");
+            return Err(Error::Auth);
+        }
+        Err(Error::RateLimited)
+    }
+}
+
+pub struct Playlist {
+    pub tracks: Vec<TrackInfo>,
+    pub local: usize,
+    pub episodes: usize,
+    pub unavailable: usize,
+}
+
+impl Playlist {
+    pub fn skipped(&self) -> Vec<(Skip, usize)> {
+        [
+            (Skip::LocalFiles, self.local),
+            (Skip::Episodes, self.episodes),
+            (Skip::Unavailable, self.unavailable),
+        ]
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .collect()
+    }
+}
+
+pub async fn playlist(uri: &str) -> Result<Playlist, Error> {
+    let spotify = SPOTIFY.read().unwrap().clone().ok_or(Error::NotConfigured)?;
+
+    let id = uri
+        .split('/')
+        .last()
+        .and_then(|id| id.split('?').next())
+        .ok_or(Error::Parse)?;
+    let mut url = format!(
+        "https://api.spotify.com/v1/playlists/{id}/tracks?market={}&limit=100&fields=next%2Citems(is_local%2Ctrack(type%2Cname%2Cduration_ms%2Cexternal_ids(isrc)%2Cartists(name)))",
+        spotify.market
     );
 
-    let result = client.execute(reqwest).await.unwrap();
-    let json: Value = serde_json::from_str(&result.text().await.unwrap()).unwrap();
-    let map = match json.as_object().unwrap().get("tracks") {
-        Some(val) => val
-            .as_object()
-            .unwrap()
-            .get("items")
-            .unwrap()
-            .as_array()
-            .unwrap(),
-        None => return None,
+    let mut playlist = Playlist {
+        tracks: Vec::new(),
+        local: 0,
+        episodes: 0,
+        unavailable: 0,
     };
+    loop {
+        let json = spotify.get(&url).await?;
+        let items = json.get("items").and_then(Value::as_array).ok_or(Error::Parse)?;
+
+        for obj in items {
+            if obj.get("is_local").and_then(Value::as_bool).unwrap_or(false) {
+                playlist.local += 1;
+                continue;
+            }
+            let track = match obj.get("track") {
+                Some(track) if !track.is_null() => track,
+                _ => {
+                    playlist.unavailable += 1;
+                    continue;
+                }
+            };
+            if track.get("type").and_then(Value::as_str) == Some("episode") {
+                playlist.episodes += 1;
+                continue;
+            }
+            let title = track.get("name").and_then(Value::as_str);
+            let artist = track
+                .get("artists")
+                .and_then(Value::as_array)
+                .and_then(|artists| artists.first())
+                .and_then(|artist| artist.get("name"))
+                .and_then(Value::as_str);
+            match (title, artist) {
+                (Some(title), Some(artist)) => playlist.tracks.push(TrackInfo {
+                    title: title.to_string(),
+                    artist: artist.to_string(),
+                    duration: track
+                        .get("duration_ms")
+                        .and_then(Value::as_u64)
+                        .map(Duration::from_millis),
+                    isrc: track
+                        .get("external_ids")
+                        .and_then(|ids| ids.get("isrc"))
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                }),
+                _ => playlist.unavailable += 1,
+            }
+        }
+
+        match json.get("next").and_then(Value::as_str) {
+            Some(next) => url = next.to_string(),
+            None => break,
+        }
+    }
+    Ok(playlist)
+}
+
+pub struct SpotifyResolver;
+
+#[async_trait]
+impl Resolver for SpotifyResolver {
+    fn name(&self) -> &'static str {
+        "spotify"
+    }
+
+    fn claims(&self, query: &str) -> bool {
+        host_matches(query, &["open.spotify.com"]) && query.contains("playlist")
+    }
 
-    let mut vec: Vec<String> = Vec::new();
-    for obj in map {
-        let track = obj.get("track").unwrap();
-        let title = track.get("name").unwrap().as_str().unwrap();
-        let artists = track.get("artists").unwrap().as_array().unwrap();
-        let artist = artists[0].get("name").unwrap().as_str().unwrap();
-        vec.push(title.to_string() + " - " + artist);
+    async fn resolve(&self, query: &str) -> Result<Resolved, resolver::Error> {
+        let playlist = playlist(query).await?;
+        Ok(Resolved::Playlist(resolver::Playlist {
+            skipped: playlist.skipped(),
+            entries: playlist.tracks.into_iter().map(Entry::Track).collect(),
+        }))
     }
-    Some(vec)
 }
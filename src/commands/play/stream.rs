@@ -0,0 +1,162 @@
+use std::{
+    io,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use log::{error, trace, warn};
+use reqwest::{
+    header::{CONTENT_RANGE, RANGE},
+    StatusCode,
+};
+use songbird::input::{
+    children_to_reader,
+    error::{Error, Result},
+    Codec, Container, Input, Metadata,
+};
+use tokio::{io::AsyncWriteExt, process::ChildStdin};
+
+use super::CLIENT;
+
+/// googlevideo throttles connections downloading more than this at once.
+const CHUNK_SIZE: u64 = 10 * 1024 * 1024;
+const MAX_RETRIES: usize = 3;
+/// Throttled chunks can take minutes, far longer than the client allows other requests.
+const CHUNK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+
+const FFMPEG_ARGS: [&str; 9] = [
+    "-f",
+    "s16le",
+    "-ac",
+    "2",
+    "-ar",
+    "48000",
+    "-acodec",
+    "pcm_f32le",
+    "-",
+];
+
+/// Lets ffmpeg read `url` directly, works for both progressive files and HLS playlists.
+pub fn ffmpeg(url: &str, pre_args: &[&str], metadata: Metadata) -> Result<Input> {
+    let ffmpeg = Command::new("ffmpeg")
+        .args(pre_args)
+        .arg("-i")
+        .arg(url)
+        .args(FFMPEG_ARGS)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    Ok(Input::new(
+        true,
+        children_to_reader::<f32>(vec![ffmpeg]),
+        Codec::FloatPcm,
+        Container::Raw,
+        Some(metadata),
+    ))
+}
+
+/// Downloads `url` in chunked range requests and feeds it to ffmpeg through stdin.
+pub fn http(url: &str, pre_args: &[&str], metadata: Metadata) -> Result<Input> {
+    let mut ffmpeg = Command::new("ffmpeg")
+        .args(pre_args)
+        .arg("-i")
+        .arg("-")
+        .args(FFMPEG_ARGS)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let stdin = ChildStdin::from_std(ffmpeg.stdin.take().ok_or(Error::Stdout)?)?;
+
+    let url = url.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = download(&url, stdin).await {
+            error!("Streaming {url} failed: {e}");
+        }
+    });
+
+    Ok(Input::new(
+        true,
+        children_to_reader::<f32>(vec![ffmpeg]),
+        Codec::FloatPcm,
+        Container::Raw,
+        Some(metadata),
+    ))
+}
+
+/// Other failures, like 403 for an expired url, fail the same way every time.
+fn retryable(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout() || e.status().map_or(false, |status| status.is_server_error())
+}
+
+async fn download(url: &str, mut stdin: ChildStdin) -> io::Result<()> {
+    let mut offset = 0;
+    let mut total = None;
+    let mut failures = 0;
+
+    while total.map_or(true, |total| offset < total) {
+        let range = format!("bytes={}-{}", offset, offset + CHUNK_SIZE - 1);
+        let response = match CLIENT.get(url).header(RANGE, range).timeout(CHUNK_TIMEOUT).send().await {
+            // Asked past the end of a stream whose length was not given
+            Ok(response) if response.status() == StatusCode::RANGE_NOT_SATISFIABLE && offset > 0 => return Ok(()),
+            response => response.and_then(|r| r.error_for_status()),
+        };
+        let mut response = match response {
+            Ok(response) => response,
+            Err(e) if retryable(&e) && failures < MAX_RETRIES => {
+                failures += 1;
+                warn!("Chunk at {offset} of {url} failed, retrying: {e}");
+                tokio::time::sleep(Duration::from_secs(failures as u64)).await;
+                continue;
+            }
+            Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e)),
+        };
+
+        let partial = response.status() == StatusCode::PARTIAL_CONTENT;
+        if partial {
+            // Content-Range looks like `bytes 0-1023/4096`
+            total = response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|h| h.to_str().ok())
+                .and_then(|h| h.rsplit('/').next())
+                .and_then(|t| t.parse().ok());
+        }
+
+        let start = offset;
+        loop {
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    if let Err(e) = stdin.write_all(&chunk).await {
+                        // ffmpeg is gone when track got stopped or skipped
+                        if e.kind() == io::ErrorKind::BrokenPipe {
+                            trace!("Stopped streaming {url}");
+                            return Ok(());
+                        }
+                        return Err(e);
+                    }
+                    offset += chunk.len() as u64;
+                }
+                Ok(None) => {
+                    failures = 0;
+                    break;
+                }
+                // Without ranges the download cannot pick up where it dropped
+                Err(e) if partial && failures < MAX_RETRIES => {
+                    failures += 1;
+                    warn!("Connection to {url} dropped at {offset}, resuming: {e}");
+                    break;
+                }
+                Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e)),
+            }
+        }
+
+        // Servers ignoring ranges send everything in one go, and a short chunk is the last one
+        if !partial || (total.is_none() && failures == 0 && offset - start < CHUNK_SIZE) {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
@@ -1,7 +1,85 @@
-use std::process::{Command, Stdio};
+use std::{
+    process::{Command, Stdio},
+    sync::LazyLock,
+    time::Duration,
+};
 
-pub fn _playlist(uri: &str) -> Option<Vec<String>> {
-    if let Ok(output) = Command::new("youtube-dl")
+use log::warn;
+use serenity::async_trait;
+use songbird::input::{error::Result, Restartable};
+use tokio::sync::OnceCell;
+
+use super::resolver::{self, is_url, Resolved, Resolver};
+
+static AVAILABLE: LazyLock<OnceCell<bool>> = LazyLock::new(OnceCell::new);
+
+/// Whether yt-dlp can be run at all, checked once.
+pub async fn available() -> bool {
+    *AVAILABLE
+        .get_or_init(|| async {
+            let found = tokio::process::Command::new("yt-dlp")
+                .arg("--version")
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .await
+                .map_or(false, |status| status.success());
+            if !found {
+                warn!("yt-dlp not found, searches and track matching are disabled");
+            }
+            found
+        })
+        .await
+}
+
+pub struct YtDlpResolver;
+
+#[async_trait]
+impl Resolver for YtDlpResolver {
+    fn name(&self) -> &'static str {
+        "yt-dlp"
+    }
+
+    fn claims(&self, _query: &str) -> bool {
+        true
+    }
+
+    async fn resolve(&self, query: &str) -> std::result::Result<Resolved, resolver::Error> {
+        if !is_url(query) {
+            return Ok(Resolved::Track(ytdlp_search(query, true).await?));
+        }
+        if query.contains("playlist") {
+            return match playlist(query).await {
+                Some(entries) if !entries.is_empty() => Ok(Resolved::Playlist(resolver::Playlist {
+                    entries: resolver::entries(entries),
+                    skipped: Vec::new(),
+                })),
+                _ => Err(resolver::Error::NothingFound),
+            };
+        }
+        Ok(Resolved::Track(ytdlp(query.to_string(), true).await?))
+    }
+}
+
+pub async fn ytdlp<P: AsRef<str> + Send + Clone + Sync + 'static>(uri: P, lazy: bool) -> Result<Restartable> {
+    Restartable::ytdl(uri, lazy).await
+}
+
+pub async fn ytdlp_search(query: impl AsRef<str>, lazy: bool) -> Result<Restartable> {
+    Restartable::ytdl_search(query, lazy).await
+}
+
+pub async fn playlist(uri: &str) -> Option<Vec<String>> {
+    let uri = uri.to_string();
+    tokio::task::spawn_blocking(move || _playlist(&uri))
+        .await
+        .ok()
+        .flatten()
+}
+
+fn _playlist(uri: &str) -> Option<Vec<String>> {
+    if let Ok(output) = Command::new("yt-dlp")
         .args([
             "-f",
             "webm[abr>0]/bestaudio/best",
@@ -44,3 +122,63 @@ pub fn _playlist(uri: &str) -> Option<Vec<String>> {
         None
     }
 }
+
+pub struct SearchResult {
+    pub url: String,
+    pub title: String,
+    pub channel: Option<String>,
+    pub duration: Option<Duration>,
+    pub thumbnail: Option<String>,
+}
+
+pub async fn search(query: &str, count: usize) -> Vec<SearchResult> {
+    if !available().await {
+        return Vec::new();
+    }
+    let output = tokio::process::Command::new("yt-dlp")
+        .args(["--flat-playlist", "--dump-single-json", "--skip-download"])
+        .arg(format!("ytsearch{count}:{query}"))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        // Callers giving up on slow searches drop this future, yt-dlp should not outlive it
+        .kill_on_drop(true)
+        .output()
+        .await;
+    output
+        .ok()
+        .and_then(|output| parse_search(&output.stdout))
+        .unwrap_or_default()
+}
+
+fn parse_search(stdout: &[u8]) -> Option<Vec<SearchResult>> {
+    let json: serde_json::Value = serde_json::from_slice(stdout).ok()?;
+    let entries = json.get("entries").and_then(serde_json::Value::as_array)?;
+    Some(
+        entries
+            .iter()
+            .filter_map(|entry| {
+                let id = entry.get("id").and_then(serde_json::Value::as_str)?;
+                Some(SearchResult {
+                    url: format!("https://www.youtube.com/watch?v={id}"),
+                    title: entry.get("title").and_then(serde_json::Value::as_str)?.to_string(),
+                    channel: entry
+                        .get("channel")
+                        .or_else(|| entry.get("uploader"))
+                        .and_then(serde_json::Value::as_str)
+                        .map(str::to_string),
+                    duration: entry
+                        .get("duration")
+                        .and_then(serde_json::Value::as_f64)
+                        .map(Duration::from_secs_f64),
+                    thumbnail: entry
+                        .get("thumbnails")
+                        .and_then(serde_json::Value::as_array)
+                        .and_then(|thumbnails| thumbnails.last())
+                        .and_then(|thumbnail| thumbnail.get("url"))
+                        .and_then(serde_json::Value::as_str)
+                        .map(str::to_string),
+                })
+            })
+            .collect(),
+    )
+}
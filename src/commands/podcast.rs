@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use log::warn;
+use serenity::{
+    async_trait,
+    builder::CreateApplicationCommand,
+    model::prelude::{
+        command::CommandOptionType,
+        interaction::application_command::ApplicationCommandInteraction,
+    },
+    prelude::Context,
+};
+
+use super::{
+    edit_msg,
+    play::{self, podcast},
+    registry::Command,
+    send_msg,
+    string_option,
+    truncate,
+    user_channel,
+};
+use crate::{
+    errors::Result,
+    locale::{Lang, Localize},
+};
+
+const SELECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct Podcast;
+
+#[async_trait]
+impl Command for Podcast {
+    fn name(&self) -> &'static str {
+        "podcast"
+    }
+
+    fn register<'a>(&self, command: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        command
+            .create_option(|option| {
+                option
+                    .localized_name("feed", "command.podcast.feed")
+                    .localized_description("command.podcast.feed")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+    }
+
+    fn cooldown(&self) -> Duration {
+        Duration::from_secs(5)
+    }
+
+    async fn run(&self, interaction: &ApplicationCommandInteraction, ctx: &Context) -> Result<()> {
+        let uri = string_option(interaction, "feed");
+        let user = &interaction.user;
+        let voice_channel = user_channel(ctx, interaction)?;
+        let lang = Lang::of(interaction);
+
+        send_msg(ctx, interaction, &lang.tr("processing")).await?;
+        let feed = match podcast::feed(&uri).await {
+            Some(feed) if !feed.episodes.is_empty() => feed,
+            Some(_) => {
+                edit_msg(ctx, interaction, &lang.tr("podcast.no_episodes")).await?;
+                return Ok(());
+            }
+            None => {
+                edit_msg(ctx, interaction, &lang.tr("podcast.cannot_load")).await?;
+                return Ok(());
+            }
+        };
+
+        let untitled = lang.tr("podcast.untitled");
+        let unknown_date = lang.tr("podcast.unknown_date");
+        let msg = interaction
+            .edit_original_interaction_response(&ctx.http, |response| {
+                response
+                    .content(lang.tr_with("podcast.pick", &[("title", &feed.title)]))
+                    .components(|components| {
+                        components.create_action_row(|row| {
+                            row.create_select_menu(|menu| {
+                                menu.custom_id("podcast_episode")
+                                    .placeholder(lang.tr("podcast.placeholder"))
+                                    .options(|options| {
+                                        for (index, episode) in feed.episodes.iter().enumerate() {
+                                            options.create_option(|option| {
+                                                option
+                                                    .label(truncate(episode.title.as_deref().unwrap_or(&untitled), 100))
+                                                    .value(index)
+                                                    .description(episode.published.as_deref().unwrap_or(&unknown_date))
+                                            });
+                                        }
+                                        options
+                                    })
+                            })
+                        })
+                    })
+            })
+            .await?;
+
+        let selected = msg
+            .await_component_interaction(ctx)
+            .author_id(user.id)
+            .timeout(SELECT_TIMEOUT)
+            .await;
+        let selected = match selected {
+            Some(selected) => selected,
+            None => {
+                if let Err(e) = interaction
+                    .edit_original_interaction_response(&ctx.http, |response| {
+                        response.content(lang.tr("podcast.nothing_selected")).components(|c| c)
+                    })
+                    .await
+                {
+                    warn!("{e}");
+                }
+                return Ok(());
+            }
+        };
+
+        let episodes = &feed.episodes;
+        play::queue_selected(
+            ctx,
+            interaction,
+            &selected,
+            voice_channel,
+            "podcast.nothing_selected",
+            move |index| async move {
+                let episode = episodes.get(index).cloned().ok_or_else(|| lang.tr("podcast.nothing_selected"))?;
+                podcast::episode(episode, true).await.map_err(|_| lang.tr("podcast.cannot_play"))
+            },
+        )
+        .await
+    }
+}
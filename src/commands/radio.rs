@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use log::trace;
+use reqwest::Url;
+use serenity::{
+    async_trait,
+    builder::CreateApplicationCommand,
+    model::prelude::{command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction},
+    prelude::Context,
+};
+
+use super::{
+    edit_msg,
+    guild_id,
+    manager,
+    play::{self, recommend, resolver::REGISTRY},
+    registry::Command,
+    send_msg,
+    user_channel,
+};
+use crate::{
+    errors::Result,
+    locale::{Lang, Localize},
+};
+
+const RADIO_TRACKS: usize = 10;
+const SUGGESTIONS: usize = 10;
+
+pub struct Radio;
+
+#[async_trait]
+impl Command for Radio {
+    fn name(&self) -> &'static str {
+        "radio"
+    }
+
+    fn register<'a>(&self, command: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        command
+            .create_option(|option| {
+                option
+                    .localized_name("start", "command.radio.start")
+                    .localized_description("command.radio.start")
+                    .kind(CommandOptionType::SubCommand)
+            })
+            .create_option(|option| {
+                option
+                    .localized_name("suggest", "command.radio.suggest")
+                    .localized_description("command.radio.suggest")
+                    .kind(CommandOptionType::SubCommand)
+            })
+    }
+
+    fn cooldown(&self) -> Duration {
+        Duration::from_secs(30)
+    }
+
+    async fn run(&self, interaction: &ApplicationCommandInteraction, ctx: &Context) -> Result<()> {
+        let guild_id = guild_id(interaction)?;
+        let lang = Lang::of(interaction);
+        let subcommand = interaction
+            .data
+            .options
+            .first()
+            .map(|option| option.name.as_str())
+            .unwrap_or("start");
+
+        if subcommand == "suggest" {
+            let mut suggestions = recommend::suggest(guild_id, SUGGESTIONS).await;
+            // Anything else would break out of the markdown link
+            suggestions.retain(|suggestion| {
+                Url::parse(&suggestion.url).map_or(false, |url| matches!(url.scheme(), "http" | "https"))
+            });
+            if suggestions.is_empty() {
+                return send_msg(ctx, interaction, &lang.tr("radio.no_history_suggest")).await;
+            }
+            let list: Vec<String> = suggestions
+                .iter()
+                .enumerate()
+                .map(|(index, suggestion)| format!("`{}.` [{}]({})", index + 1, suggestion.title, suggestion.url))
+                .collect();
+            return send_msg(ctx, interaction, &lang.tr_with("radio.suggested", &[("list", &list.join("\n"))])).await;
+        }
+
+        let voice_channel = user_channel(ctx, interaction)?;
+
+        let suggestions = recommend::suggest(guild_id, RADIO_TRACKS).await;
+        if suggestions.is_empty() {
+            return send_msg(ctx, interaction, &lang.tr("radio.no_history_start")).await;
+        }
+        send_msg(ctx, interaction, &lang.tr("radio.starting")).await?;
+
+        let handler_lock = manager(ctx).await?.get_or_insert(guild_id);
+        let mut queued = 0;
+        for suggestion in suggestions {
+            if let Ok(source) = REGISTRY.resolve_track(&suggestion.url).await {
+                let metadata = play::enqueue(ctx, interaction, &handler_lock, voice_channel, source).await?;
+                trace!("{}", play::get_msg(metadata, &interaction.user, lang));
+                queued += 1;
+            }
+        }
+        edit_msg(ctx, interaction, &lang.tr_with("radio.queued", &[("count", &queued)])).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use log::{info, trace, warn};
+use rusqlite::{params, OptionalExtension};
+use serenity::{
+    builder::CreateApplicationCommands,
+    http::Http,
+    model::{application::command::Command as ApplicationCommand, prelude::GuildId},
+    prelude::Context,
+};
+
+use super::COMMANDS;
+use crate::{
+    config::{self, Registration},
+    db,
+};
+
+/// Scope under which the hash of global commands is stored, guilds use their id.
+const GLOBAL: u64 = 0;
+
+/// Hash of the commands as sent to Discord, so changes to options or descriptions count too.
+fn commands_hash() -> String {
+    let mut commands = CreateApplicationCommands::default();
+    COMMANDS.create(&mut commands);
+    hash(&commands)
+}
+
+fn hash(commands: &CreateApplicationCommands) -> String {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(&commands.0).unwrap_or_default().hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+async fn stored_hash(scope: u64) -> Option<String> {
+    db::run(move |db| {
+        db.query_row(
+            "SELECT hash FROM registrations WHERE scope = ?1",
+            params![scope],
+            |row| row.get(0),
+        )
+        .optional()
+    })
+    .await
+    .unwrap_or_else(|e| {
+        warn!("Cannot read registration of {scope}: {e}");
+        None
+    })
+}
+
+async fn store_hash(scope: u64, hash: Option<String>) {
+    let result = db::run(move |db| match hash {
+        Some(hash) => db.execute(
+            "INSERT OR REPLACE INTO registrations (scope, hash) VALUES (?1, ?2)",
+            params![scope, hash],
+        ),
+        None => db.execute("DELETE FROM registrations WHERE scope = ?1", params![scope]),
+    })
+    .await;
+    if let Err(e) = result {
+        warn!("Cannot store registration of {scope}: {e}");
+    }
+}
+
+/// Whether Discord has the same commands as the bot, by name.
+fn registered(existing: &[ApplicationCommand]) -> bool {
+    let mut registered: Vec<&str> = existing.iter().map(|command| command.name.as_str()).collect();
+    let mut names: Vec<&str> = COMMANDS.names().collect();
+    registered.sort_unstable();
+    names.sort_unstable();
+    registered == names
+}
+
+/// Stored hashes miss commands removed by hand and ones registered before hashes were stored,
+/// so what Discord has decides too when it can be read.
+fn up_to_date(stored: Option<&str>, hash: &str, existing: Option<&[ApplicationCommand]>) -> bool {
+    stored == Some(hash) && existing.map_or(true, registered)
+}
+
+/// Commands are in place when Discord says so, or when they were stored as registered otherwise.
+fn present(stored: Option<&str>, existing: Option<&[ApplicationCommand]>) -> bool {
+    existing.map_or(stored.is_some(), |existing| !existing.is_empty())
+}
+
+/// Drops the stored hash of a guild the bot left, its commands go with it.
+pub async fn forget(guild_id: GuildId) {
+    store_hash(guild_id.0, None).await;
+}
+
+fn wanted_in(guild_id: GuildId) -> bool {
+    let config = config::get();
+    config.discord.registration == Registration::Guilds
+        && (config.discord.guilds_ids.is_empty() || config.discord.guilds_ids.contains(&guild_id.0))
+}
+
+/// Registers or removes global commands, depending on registration mode.
+pub async fn global(http: &Http) {
+    let stored = stored_hash(GLOBAL).await;
+    let existing = match ApplicationCommand::get_global_application_commands(http).await {
+        Ok(existing) => Some(existing),
+        Err(e) => {
+            warn!("Cannot read global commands: {e}");
+            None
+        }
+    };
+    if config::get().discord.registration == Registration::Global {
+        let hash = commands_hash();
+        if up_to_date(stored.as_deref(), &hash, existing.as_deref()) {
+            trace!("Global commands did not change");
+            return;
+        }
+        match ApplicationCommand::set_global_application_commands(http, |commands| COMMANDS.create(commands)).await {
+            Ok(_) => {
+                info!("Registered global commands");
+                store_hash(GLOBAL, Some(hash)).await;
+            }
+            Err(e) => warn!("Cannot register global commands: {e}"),
+        }
+    } else if present(stored.as_deref(), existing.as_deref()) {
+        match ApplicationCommand::set_global_application_commands(http, |commands| commands).await {
+            Ok(_) => {
+                info!("Removed global commands");
+                store_hash(GLOBAL, None).await;
+            }
+            Err(e) => warn!("Cannot remove global commands: {e}"),
+        }
+    }
+}
+
+/// Registers commands in the guild, or removes them when they should not be there.
+///
+/// Setting commands replaces all the previous ones, so commands the bot no longer has are dropped too.
+pub async fn guild(http: &Http, guild_id: GuildId) {
+    let stored = stored_hash(guild_id.0).await;
+    let existing = match guild_id.get_application_commands(http).await {
+        Ok(existing) => Some(existing),
+        Err(e) => {
+            warn!("Cannot read commands of {guild_id}: {e}");
+            None
+        }
+    };
+    if wanted_in(guild_id) {
+        let hash = commands_hash();
+        if up_to_date(stored.as_deref(), &hash, existing.as_deref()) {
+            trace!("Commands in {guild_id} did not change");
+            return;
+        }
+        match guild_id
+            .set_application_commands(http, |commands| COMMANDS.create(commands))
+            .await
+        {
+            Ok(_) => {
+                info!("Registered commands in {guild_id}");
+                store_hash(guild_id.0, Some(hash)).await;
+            }
+            Err(e) => warn!("Cannot register commands in {guild_id}: {e}"),
+        }
+    } else if present(stored.as_deref(), existing.as_deref()) {
+        match guild_id.set_application_commands(http, |commands| commands).await {
+            Ok(_) => {
+                info!("Removed commands from {guild_id}");
+                store_hash(guild_id.0, None).await;
+            }
+            Err(e) => warn!("Cannot remove commands from {guild_id}: {e}"),
+        }
+    }
+}
+
+/// Brings registration of every guild the bot is in in line with the config.
+pub async fn sync(ctx: &Context) {
+    global(&ctx.http).await;
+    for guild_id in ctx.cache.guilds() {
+        guild(&ctx.http, guild_id).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(description: &str) -> CreateApplicationCommands {
+        let mut commands = CreateApplicationCommands::default();
+        commands.create_application_command(|command| command.name("ping").description(description));
+        commands
+    }
+
+    #[test]
+    fn hash_is_stable() {
+        assert_eq!(commands_hash(), commands_hash());
+        assert_eq!(commands_hash().len(), 16);
+    }
+
+    #[test]
+    fn hash_covers_descriptions() {
+        assert_eq!(hash(&commands("A ping command")), hash(&commands("A ping command")));
+        assert_ne!(hash(&commands("A ping command")), hash(&commands("Checks if the bot is alive")));
+    }
+}
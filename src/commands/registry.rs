@@ -0,0 +1,170 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::trace;
+use serenity::{
+    async_trait,
+    builder::{CreateApplicationCommand, CreateApplicationCommands},
+    model::{
+        prelude::{
+            interaction::{application_command::ApplicationCommandInteraction, autocomplete::AutocompleteInteraction},
+            UserId,
+        },
+        Permissions,
+    },
+    prelude::Context,
+};
+
+use super::{autoplay, like, pause, ping, play, podcast, radio, repeat, resume, search, settings, skip, stop};
+use crate::{
+    errors::{Error, Result},
+    locale::Localize,
+};
+
+pub static COMMANDS: LazyLock<Registry> = LazyLock::new(|| {
+    let mut registry = Registry::new();
+    registry.register(ping::Ping);
+    registry.register(autoplay::Autoplay);
+    registry.register(like::Like);
+    registry.register(play::Play);
+    registry.register(podcast::Podcast);
+    registry.register(radio::Radio);
+    registry.register(skip::Skip);
+    registry.register(stop::Stop);
+    registry.register(pause::Pause);
+    registry.register(resume::Resume);
+    registry.register(search::Search);
+    registry.register(repeat::Repeat);
+    registry.register(settings::SettingsCommand);
+    registry
+});
+
+#[async_trait]
+pub trait Command: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Fills in options, name and description are set by the registry from the translations.
+    fn register<'a>(&self, command: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        command
+    }
+
+    /// Permissions members need to see and run the command.
+    fn permissions(&self) -> Option<Permissions> {
+        None
+    }
+
+    /// How long each user has to wait before running the command again.
+    fn cooldown(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    async fn run(&self, interaction: &ApplicationCommandInteraction, ctx: &Context) -> Result<()>;
+
+    async fn autocomplete(&self, _interaction: &AutocompleteInteraction, _ctx: &Context) {}
+}
+
+pub struct Registry {
+    commands: Vec<Box<dyn Command>>,
+    last_used: Mutex<HashMap<(UserId, &'static str), Instant>>,
+}
+
+impl Registry {
+    fn new() -> Self {
+        Registry {
+            commands: Vec::new(),
+            last_used: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn register(&mut self, command: impl Command + 'static) {
+        self.commands.push(Box::new(command));
+    }
+
+    fn get(&self, name: &str) -> Option<&dyn Command> {
+        self.commands
+            .iter()
+            .find(|command| command.name() == name)
+            .map(|command| command.as_ref())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.commands.iter().map(|command| command.name())
+    }
+
+    fn longest_cooldown(&self) -> Duration {
+        self.commands
+            .iter()
+            .map(|command| command.cooldown())
+            .max()
+            .unwrap_or_default()
+    }
+
+    pub fn create<'a>(&self, commands: &'a mut CreateApplicationCommands) -> &'a mut CreateApplicationCommands {
+        for command in &self.commands {
+            commands.create_application_command(|create| {
+                let name = command.name();
+                create
+                    .localized_name(name, &format!("command.{name}"))
+                    .localized_description(&format!("command.{name}.description"));
+                if let Some(permissions) = command.permissions() {
+                    create.default_member_permissions(permissions);
+                }
+                command.register(create)
+            });
+        }
+        commands
+    }
+
+    pub async fn run(&self, interaction: &ApplicationCommandInteraction, ctx: &Context) -> Result<()> {
+        let name = interaction.data.name.as_str();
+        let command = self.get(name).ok_or_else(|| Error::UnknownCommand(name.to_string()))?;
+
+        // Discord hides commands from members lacking permissions, but server admins can override that
+        if let Some(required) = command.permissions() {
+            let permissions = interaction
+                .member
+                .as_ref()
+                .and_then(|member| member.permissions)
+                .unwrap_or_else(Permissions::empty);
+            if !permissions.contains(required) {
+                return Err(Error::MissingPermissions);
+            }
+        }
+
+        let cooldown = command.cooldown();
+        let key = (interaction.user.id, command.name());
+        if !cooldown.is_zero() {
+            let last_used = self.last_used.lock().unwrap();
+            if let Some(left) = last_used
+                .get(&key)
+                .map(|used| cooldown.saturating_sub(used.elapsed()))
+                .filter(|left| !left.is_zero())
+            {
+                return Err(Error::Cooldown(left));
+            }
+        }
+
+        trace!("{} ran /{name}", interaction.user.name);
+        command.run(interaction, ctx).await?;
+
+        // Failed runs, like ones refused for not being in voice, do not count
+        if !cooldown.is_zero() {
+            let mut last_used = self.last_used.lock().unwrap();
+            let now = Instant::now();
+            // Entries older than any cooldown no longer block anyone
+            let longest = self.longest_cooldown();
+            last_used.retain(|_, used| now - *used < longest);
+            last_used.insert(key, now);
+        }
+        Ok(())
+    }
+
+    pub async fn autocomplete(&self, interaction: &AutocompleteInteraction, ctx: &Context) {
+        if let Some(command) = self.get(&interaction.data.name) {
+            command.autocomplete(interaction, ctx).await;
+        }
+    }
+}
@@ -1,53 +1,30 @@
 use serenity::{
-    builder::CreateApplicationCommand,
+    async_trait,
     model::prelude::interaction::application_command::ApplicationCommandInteraction,
     prelude::Context,
 };
 
-use super::send_msg;
-
-pub async fn run(interaction: &ApplicationCommandInteraction, ctx: &Context) {
-    let guild_id = interaction.guild_id.unwrap();
-    let guild = ctx.cache.guild(guild_id).unwrap();
-    let menager = songbird::get(ctx)
-        .await
-        .expect("Failed to get manager")
-        .clone();
+use super::{
+    guard::{Guard, Voice},
+    registry::Command,
+    send_msg,
+};
+use crate::errors::Result;
 
-    let handler_lock = match menager.get(guild_id) {
-        Some(handler) => handler,
-        None => return send_msg(ctx, interaction, "Bot is not connected to voice channel").await,
-    };
-    let handler = handler_lock.lock().await;
+pub struct Resume;
 
-    let voice_channel = match guild
-        .voice_states
-        .get(&interaction.user.id)
-        .and_then(|voice_state| voice_state.channel_id) 
-    {
-        Some(v) => v.0,                                                                              
-        None => return send_msg(ctx, interaction, "You are not connected to voice channel").await
-    };
-    let bot_voice_channel = match handler.current_channel() {
-        Some(v) => v.0,
-        None => return send_msg(ctx, interaction, "Bot is not connected to voice channel").await
-        
-    };
+#[async_trait]
+impl Command for Resume {
+    fn name(&self) -> &'static str {
+        "resume"
+    }
 
-    if voice_channel == bot_voice_channel {
+    async fn run(&self, interaction: &ApplicationCommandInteraction, ctx: &Context) -> Result<()> {
+        let Voice { handler, lang, .. } = Guard::new().same_channel().dj().check(ctx, interaction).await?;
         if handler.queue().resume().is_ok() {
-            send_msg(ctx, interaction, "Song resumed").await
+            send_msg(ctx, interaction, &lang.tr("resume.done")).await
         } else {
-            send_msg(ctx, interaction, "Cannot resume song").await
-
+            send_msg(ctx, interaction, &lang.tr("resume.failed")).await
         }
-    } else {
-        send_msg(ctx, interaction, "You are not connected to voice channel with bot").await
     }
 }
-
-pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
-    command
-        .name("resume")
-        .description("Resume current song")
-}
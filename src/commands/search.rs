@@ -0,0 +1,207 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use serenity::{
+    async_trait,
+    builder::{CreateApplicationCommand, CreateEmbed},
+    model::prelude::{
+        command::CommandOptionType,
+        interaction::{application_command::ApplicationCommandInteraction, InteractionResponseType},
+    },
+    prelude::Context,
+};
+
+use super::{
+    edit_msg,
+    format_duration,
+    guild_id,
+    play::{self, resolver::REGISTRY, youtube},
+    registry::Command,
+    send_msg,
+    string_option,
+    truncate,
+    user_channel,
+};
+use crate::{
+    errors::Result,
+    locale::{Lang, Localize},
+    settings,
+};
+
+const DEFAULT_RESULTS: u64 = 5;
+const MAX_RESULTS: u64 = 10;
+const SELECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct Search;
+
+#[async_trait]
+impl Command for Search {
+    fn name(&self) -> &'static str {
+        "search"
+    }
+
+    fn register<'a>(&self, command: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        command
+            .create_option(|option| {
+                option
+                    .localized_name("query", "command.search.query")
+                    .localized_description("command.search.query")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .localized_name("results", "command.search.results")
+                    .localized_description("command.search.results")
+                    .kind(CommandOptionType::Integer)
+                    .min_int_value(1)
+                    .max_int_value(MAX_RESULTS)
+                    .required(false)
+            })
+    }
+
+    fn cooldown(&self) -> Duration {
+        Duration::from_secs(5)
+    }
+
+    async fn run(&self, interaction: &ApplicationCommandInteraction, ctx: &Context) -> Result<()> {
+        let query = string_option(interaction, "query");
+        let count = interaction
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == "results")
+            .and_then(|option| option.value.as_ref())
+            .and_then(|value| value.as_u64())
+            .unwrap_or(DEFAULT_RESULTS)
+            .clamp(1, MAX_RESULTS);
+
+        let user = &interaction.user;
+        let guild_id = guild_id(interaction)?;
+        let voice_channel = user_channel(ctx, interaction)?;
+        let lang = Lang::of(interaction);
+
+        send_msg(ctx, interaction, &lang.tr("search.searching")).await?;
+        let results = youtube::search(&query, count as usize).await;
+        if results.is_empty() {
+            edit_msg(ctx, interaction, &lang.tr("search.nothing_found")).await?;
+            return Ok(());
+        }
+
+        let unknown_channel = lang.tr("search.unknown_channel");
+        let embeds: Vec<CreateEmbed> = results
+            .iter()
+            .enumerate()
+            .map(|(index, result)| {
+                let mut embed = CreateEmbed(HashMap::new());
+                embed
+                    .title(format!("{}. {}", index + 1, result.title))
+                    .url(&result.url)
+                    .description(format!(
+                        "`{}` - `{}`",
+                        result.channel.as_deref().unwrap_or(&unknown_channel),
+                        result.duration.map(format_duration).unwrap_or_else(|| lang.tr("search.live"))
+                    ))
+                    .colour(16711937);
+                if let Some(thumbnail) = &result.thumbnail {
+                    embed.thumbnail(thumbnail);
+                }
+                embed
+            })
+            .collect();
+
+        let msg = interaction
+            .edit_original_interaction_response(&ctx.http, |response| {
+                response
+                    .content(lang.tr_with("search.results", &[("query", &query)]))
+                    .set_embeds(embeds)
+                    .components(|components| {
+                        components.create_action_row(|row| {
+                            row.create_select_menu(|menu| {
+                                menu.custom_id("search_result")
+                                    .placeholder(lang.tr("search.placeholder"))
+                                    .options(|options| {
+                                        for (index, result) in results.iter().enumerate() {
+                                            options.create_option(|option| {
+                                                option
+                                                    .label(truncate(&format!("{}. {}", index + 1, result.title), 100))
+                                                    .value(index)
+                                                    .description(truncate(
+                                                        result.channel.as_deref().unwrap_or(&unknown_channel),
+                                                        100,
+                                                    ))
+                                            });
+                                        }
+                                        options
+                                    })
+                            })
+                        })
+                    })
+            })
+            .await?;
+
+        // Only the user who searched can pick, everyone else gets a private note
+        let deadline = Instant::now() + SELECT_TIMEOUT;
+        let selected = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let selected = match msg.await_component_interaction(ctx).timeout(remaining).await {
+                Some(selected) => selected,
+                None => break None,
+            };
+            if selected.user.id == user.id {
+                break Some(selected);
+            }
+            let other_lang = settings::get(guild_id)
+                .language
+                .unwrap_or_else(|| Lang::from_locale(&selected.locale));
+            if let Err(e) = selected
+                .create_interaction_response(&ctx.http, |response| {
+                    response
+                        .kind(InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|message| {
+                            message
+                                .content(other_lang.tr_with("search.not_yours", &[("user", &user.name)]))
+                                .ephemeral(true)
+                        })
+                })
+                .await
+            {
+                warn!("{e}");
+            }
+        };
+        let selected = match selected {
+            Some(selected) => selected,
+            None => {
+                if let Err(e) = interaction
+                    .edit_original_interaction_response(&ctx.http, |response| {
+                        response
+                            .content(lang.tr("search.nothing_selected"))
+                            .set_embeds(Vec::new())
+                            .components(|c| c)
+                    })
+                    .await
+                {
+                    warn!("{e}");
+                }
+                return Ok(());
+            }
+        };
+
+        let results = &results;
+        play::queue_selected(
+            ctx,
+            interaction,
+            &selected,
+            voice_channel,
+            "search.nothing_selected",
+            move |index| async move {
+                let result = results.get(index).ok_or_else(|| lang.tr("search.nothing_selected"))?;
+                REGISTRY.resolve_track(&result.url).await.map_err(|e| lang.tr(e.key()))
+            },
+        )
+        .await
+    }
+}
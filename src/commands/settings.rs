@@ -0,0 +1,223 @@
+use serde_json::Value;
+use serenity::{
+    async_trait,
+    builder::CreateApplicationCommand,
+    model::{
+        prelude::{
+            command::CommandOptionType,
+            interaction::application_command::{ApplicationCommandInteraction, CommandDataOption},
+            ChannelId, ChannelType, RoleId,
+        },
+        Permissions,
+    },
+    prelude::Context,
+};
+
+use super::{guild_id, manager, registry::Command, send_msg};
+use crate::{
+    config,
+    errors::Result,
+    locale::{Lang, Localize},
+    settings::{self, Settings, MAX_VOLUME},
+};
+
+fn option<'a>(subcommand: &'a CommandDataOption, name: &str) -> Option<&'a Value> {
+    subcommand
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_ref())
+}
+
+/// Channels and roles come as snowflake strings.
+fn id(value: Option<&Value>) -> Option<u64> {
+    value.and_then(Value::as_str).and_then(|id| id.parse().ok())
+}
+
+fn count(value: Option<&Value>) -> Option<usize> {
+    value.and_then(Value::as_u64).map(|count| count as usize)
+}
+
+fn describe(settings: &Settings, lang: Lang) -> String {
+    let limits = &config::get().limits;
+    lang.tr_with(
+        "settings.summary",
+        &[
+            ("volume", &settings.volume.unwrap_or(100)),
+            (
+                "announce",
+                &settings
+                    .announce_channel
+                    .map_or(lang.tr("settings.announce_default"), |c| format!("<#{c}>")),
+            ),
+            (
+                "dj",
+                &settings
+                    .dj_role
+                    .map_or(lang.tr("settings.dj_default"), |r| format!("<@&{r}>")),
+            ),
+            (
+                "language",
+                &settings
+                    .language
+                    .map_or(lang.tr("settings.language_default"), |l| l.name().to_string()),
+            ),
+            ("playlist_songs", &settings.playlist_songs()),
+            ("playlist_songs_max", &limits.playlist_songs),
+            ("queue", &settings.queue()),
+            ("queue_max", &limits.queue),
+        ],
+    )
+}
+
+pub struct SettingsCommand;
+
+#[async_trait]
+impl Command for SettingsCommand {
+    fn name(&self) -> &'static str {
+        "settings"
+    }
+
+    fn register<'a>(&self, command: &'a mut CreateApplicationCommand) -> &'a mut CreateApplicationCommand {
+        command
+            .dm_permission(false)
+            .create_option(|option| {
+                option
+                    .localized_name("show", "command.settings.show")
+                    .localized_description("command.settings.show")
+                    .kind(CommandOptionType::SubCommand)
+            })
+            .create_option(|option| {
+                option
+                    .localized_name("volume", "command.settings.volume")
+                    .localized_description("command.settings.volume")
+                    .kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|sub| {
+                        sub.localized_name("percent", "command.settings.volume.percent")
+                            .localized_description("command.settings.volume.percent")
+                            .kind(CommandOptionType::Integer)
+                            .min_int_value(0)
+                            .max_int_value(MAX_VOLUME)
+                            .required(true)
+                    })
+            })
+            .create_option(|option| {
+                option
+                    .localized_name("announce", "command.settings.announce")
+                    .localized_description("command.settings.announce")
+                    .kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|sub| {
+                        sub.localized_name("channel", "command.settings.announce.channel")
+                            .localized_description("command.settings.announce.channel")
+                            .kind(CommandOptionType::Channel)
+                            .channel_types(&[ChannelType::Text])
+                            .required(false)
+                    })
+            })
+            .create_option(|option| {
+                option
+                    .localized_name("dj", "command.settings.dj")
+                    .localized_description("command.settings.dj")
+                    .kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|sub| {
+                        sub.localized_name("role", "command.settings.dj.role")
+                            .localized_description("command.settings.dj.role")
+                            .kind(CommandOptionType::Role)
+                            .required(false)
+                    })
+            })
+            .create_option(|option| {
+                option
+                    .localized_name("language", "command.settings.language")
+                    .localized_description("command.settings.language")
+                    .kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|sub| {
+                        sub.localized_name("language", "command.settings.language.language")
+                            .localized_description("command.settings.language.language")
+                            .kind(CommandOptionType::String)
+                            .required(false);
+                        for lang in Lang::ALL {
+                            sub.add_string_choice(lang.name(), lang.code());
+                        }
+                        sub
+                    })
+            })
+            .create_option(|option| {
+                option
+                    .localized_name("limits", "command.settings.limits")
+                    .localized_description("command.settings.limits")
+                    .kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|sub| {
+                        sub.localized_name("playlist_songs", "command.settings.limits.playlist_songs")
+                            .localized_description("command.settings.limits.playlist_songs")
+                            .kind(CommandOptionType::Integer)
+                            .min_int_value(1)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.localized_name("queue", "command.settings.limits.queue")
+                            .localized_description("command.settings.limits.queue")
+                            .kind(CommandOptionType::Integer)
+                            .min_int_value(1)
+                            .required(false)
+                    })
+            })
+    }
+
+    fn permissions(&self) -> Option<Permissions> {
+        Some(Permissions::MANAGE_GUILD)
+    }
+
+    async fn run(&self, interaction: &ApplicationCommandInteraction, ctx: &Context) -> Result<()> {
+        let guild_id = guild_id(interaction)?;
+        let subcommand = match interaction.data.options.first() {
+            Some(subcommand) => subcommand,
+            None => {
+                return send_msg(ctx, interaction, &describe(&settings::get(guild_id), Lang::of(interaction))).await;
+            }
+        };
+
+        let mut settings = settings::get(guild_id);
+        match subcommand.name.as_str() {
+            "volume" => {
+                let percent = option(subcommand, "percent")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(100)
+                    .min(MAX_VOLUME as u64) as u8;
+                settings.volume = Some(percent);
+                // Songs already queued keep the old volume otherwise
+                if let Some(handler_lock) = manager(ctx).await?.get(guild_id) {
+                    for track in handler_lock.lock().await.queue().current_queue() {
+                        track.set_volume(settings.volume())?;
+                    }
+                }
+            }
+            "announce" => settings.announce_channel = id(option(subcommand, "channel")).map(ChannelId),
+            "dj" => settings.dj_role = id(option(subcommand, "role")).map(RoleId),
+            "language" => {
+                settings.language = option(subcommand, "language")
+                    .and_then(Value::as_str)
+                    .and_then(Lang::from_code)
+            }
+            "limits" => {
+                let playlist_songs = count(option(subcommand, "playlist_songs"));
+                let queue = count(option(subcommand, "queue"));
+                // Without any option both go back to the global limits
+                if playlist_songs.is_none() && queue.is_none() {
+                    settings.playlist_songs = None;
+                    settings.queue = None;
+                } else {
+                    settings.playlist_songs = playlist_songs.or(settings.playlist_songs);
+                    settings.queue = queue.or(settings.queue);
+                }
+            }
+            _ => return send_msg(ctx, interaction, &describe(&settings, Lang::of(interaction))).await,
+        }
+
+        settings::set(guild_id, settings.clone()).await?;
+        // Confirms in the language just picked
+        let lang = Lang::of(interaction);
+        let saved = lang.tr_with("settings.saved", &[("settings", &describe(&settings, lang))]);
+        send_msg(ctx, interaction, &saved).await
+    }
+}
@@ -1,49 +1,35 @@
 use serenity::{
-    builder::CreateApplicationCommand,
+    async_trait,
     model::prelude::interaction::application_command::ApplicationCommandInteraction,
     prelude::Context,
 };
 
-use super::send_msg;
-
-pub async fn run(interaction: &ApplicationCommandInteraction, ctx: &Context) {
-    let guild_id = interaction.guild_id.unwrap();
-    let guild = ctx.cache.guild(guild_id).unwrap();
-    let menager = songbird::get(ctx)
-        .await
-        .expect("Failed to get manager")
-        .clone();
+use super::{
+    guard::{Guard, Voice},
+    play::history,
+    registry::Command,
+    send_msg,
+};
+use crate::errors::Result;
 
-    let handler_lock = match menager.get(guild_id) {
-        Some(handler) => handler,
-        None => return send_msg(ctx, interaction, "Bot is not connected to voice channel").await,
-    };
-    let handler = handler_lock.lock().await;
+pub struct Skip;
 
-    let voice_channel = match guild
-        .voice_states
-        .get(&interaction.user.id)
-        .and_then(|voice_state| voice_state.channel_id) 
-    {
-        Some(v) => v.0,                                                                              
-        None => return send_msg(ctx, interaction, "You are not connected to voice channel").await
-    };
-    let bot_voice_channel = match handler.current_channel() {
-        Some(v) => v.0,
-        None => return send_msg(ctx, interaction, "Bot is not connected to voice channel").await
-        
-    };
+#[async_trait]
+impl Command for Skip {
+    fn name(&self) -> &'static str {
+        "skip"
+    }
 
-    if voice_channel == bot_voice_channel {
+    async fn run(&self, interaction: &ApplicationCommandInteraction, ctx: &Context) -> Result<()> {
+        let Voice { guild_id, handler, lang, .. } = Guard::new().same_channel().dj().check(ctx, interaction).await?;
+        if let Some(url) = handler
+            .queue()
+            .current()
+            .and_then(|track| track.metadata().source_url.clone())
+        {
+            history::skipped(guild_id, url).await;
+        }
         let _ = handler.queue().skip();
-        send_msg(ctx, interaction, "Skipped song").await
-    } else {
-        send_msg(ctx, interaction, "You are not connected to voice channel with bot").await
+        send_msg(ctx, interaction, &lang.tr("skip.done")).await
     }
 }
-
-pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
-    command
-        .name("skip")
-        .description("Skips current song")
-}
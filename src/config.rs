@@ -1,50 +1,463 @@
 use std::{
-    collections::HashMap,
-    fs::read_to_string,
-    process::exit, sync::LazyLock
+    env, fmt,
+    fs::{metadata, read_to_string},
+    future::pending,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::{Duration, SystemTime},
 };
-use log::error;
 
-static CONFIG: LazyLock<String> = LazyLock::new(|| {
-    match read_to_string("./config.json") {
-        Ok(data) => data,
-        Err(_) => {
-            error!("Cannot load config file. Does it exits?");
-            exit(1)
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::watch,
+};
+
+const DEFAULT_PATH: &str = "./config.json";
+const ENV_PREFIX: &str = "JACABOT_";
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+static CONFIG: OnceLock<watch::Sender<Arc<Config>>> = OnceLock::new();
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    pub discord: DiscordConfig,
+    #[serde(default)]
+    pub spotify: Option<SpotifyConfig>,
+    #[serde(default)]
+    pub youtube: YoutubeConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    /// SQLite file with settings, history and saved sessions.
+    #[serde(default = "default_database")]
+    pub database: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscordConfig {
+    #[serde(default)]
+    pub token: String,
+    /// File holding the token, so it does not have to live next to the config.
+    #[serde(default)]
+    token_file: Option<PathBuf>,
+    /// Guilds to register commands in, all of them when empty.
+    #[serde(default)]
+    pub guilds_ids: Vec<u64>,
+    #[serde(default)]
+    pub registration: Registration,
+    /// Gateway shards to run, as many as Discord recommends when missing.
+    #[serde(default)]
+    pub shards: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Registration {
+    /// Commands are set in each guild separately and show up right away.
+    #[default]
+    Guilds,
+    /// Commands are set once for every guild, Discord can take up to an hour to show changes.
+    Global,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct SpotifyConfig {
+    #[serde(default)]
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: String,
+    #[serde(default)]
+    client_secret_file: Option<PathBuf>,
+    #[serde(default = "default_market")]
+    pub market: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeConfig {
+    #[serde(default = "default_backends")]
+    pub backends: Vec<Backend>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Songs queued from a single playlist, the rest is skipped.
+    pub playlist_songs: usize,
+    /// Songs waiting in the queue of a guild.
+    pub queue: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    Rustube,
+    YtDlp,
+}
+
+fn default_market() -> String {
+    "PL".to_string()
+}
+
+fn default_database() -> PathBuf {
+    PathBuf::from("./jacabot.db")
+}
+
+fn default_backends() -> Vec<Backend> {
+    vec![Backend::Rustube, Backend::YtDlp]
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            playlist_songs: 500,
+            queue: 1000,
         }
     }
-});
+}
 
-pub static DISCORD_CONFIG: LazyLock<serde_json::Value> = LazyLock::new(|| {
-    let data: HashMap<String, serde_json::Value> = match serde_json::from_str(&CONFIG) {
-        Ok(json) => json,
-        Err(e) => {
-            error!("{e}");
-            exit(1);
+impl Default for YoutubeConfig {
+    fn default() -> Self {
+        YoutubeConfig {
+            backends: default_backends(),
         }
-    };
-    match data.get("discord") {
-        Some(d) => d.to_owned(),
-        None => {
-            error!("Cannot parse config file. Invalid config?");
-            exit(1)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, serde_json::Error),
+    Env(String, String),
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(path, e) => write!(f, "Cannot read {}: {e}", path.display()),
+            Error::Parse(path, e) => write!(f, "Invalid config {}: {e}", path.display()),
+            Error::Env(name, e) => write!(f, "Invalid {name}: {e}"),
+            Error::Invalid(e) => write!(f, "Invalid config: {e}"),
+        }
+    }
+}
+
+/// Path given as `--config <path>` or the first argument, `./config.json` otherwise.
+pub fn path() -> PathBuf {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" | "--config" => return args.next().map(PathBuf::from).unwrap_or_else(|| DEFAULT_PATH.into()),
+            arg if !arg.starts_with('-') => return PathBuf::from(arg),
+            _ => {}
         }
     }
-});
+    PathBuf::from(DEFAULT_PATH)
+}
+
+/// Loads, overrides and validates config, making it available through [`get`].
+pub fn init(path: &Path) -> Result<Arc<Config>, Error> {
+    let config = Arc::new(Config::load(path)?);
+    CONFIG.get_or_init(|| watch::channel(config.clone()).0);
+    Ok(config)
+}
+
+fn sender() -> &'static watch::Sender<Arc<Config>> {
+    CONFIG.get().expect("Config used before init")
+}
+
+/// Current config. Hold on to it only as long as needed, it can be swapped by [`reload`].
+pub fn get() -> Arc<Config> {
+    sender().borrow().clone()
+}
+
+/// Receiver notified with every config which replaced the previous one.
+pub fn subscribe() -> watch::Receiver<Arc<Config>> {
+    sender().subscribe()
+}
+
+/// Replaces current config with the one in `path`. Invalid config leaves the current one in place.
+pub fn reload(path: &Path) -> Result<(), Error> {
+    let config = Config::load(path)?;
+    let old = sender().send_replace(Arc::new(config));
+    let new = get();
+    if old.discord.token != new.discord.token {
+        warn!("discord.token changed, restart to log in with the new one");
+    }
+    if old.discord.shards != new.discord.shards {
+        warn!("discord.shards changed, restart to run the new number of shards");
+    }
+    if old.database != new.database {
+        warn!("database changed, restart to open the new one");
+    }
+    Ok(())
+}
 
-pub static SPOTIFY_CONFIG: LazyLock<serde_json::Value> = LazyLock::new(|| {
-    let data: HashMap<String, serde_json::Value> = match serde_json::from_str(&CONFIG) {
-        Ok(json) => json,
+fn modified(path: &Path) -> Option<SystemTime> {
+    metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reloads config whenever its file changes or the process gets SIGHUP.
+pub async fn watch(path: PathBuf) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => Some(hangup),
         Err(e) => {
-            error!("{e}");
-            exit(1);
+            warn!("Cannot listen for SIGHUP, only file changes reload config: {e}");
+            None
         }
     };
-    match data.get("spotify") {
-        Some(d) => d.to_owned(),
-        None => {
-            error!("Cannot parse config file. Invalid config?");
-            exit(1)
+    let mut last_modified = modified(&path);
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = async {
+                match &mut hangup {
+                    Some(hangup) => hangup.recv().await,
+                    None => pending().await,
+                }
+            } => info!("Got SIGHUP, reloading config"),
+            _ = interval.tick() => {
+                let now = modified(&path);
+                if now == last_modified {
+                    continue;
+                }
+                last_modified = now;
+                info!("{} changed, reloading config", path.display());
+            }
+        }
+        match reload(&path) {
+            Ok(()) => info!("Config reloaded"),
+            Err(e) => error!("Keeping previous config: {e}"),
+        }
+    }
+}
+
+fn var(name: &str) -> Option<String> {
+    env::var(format!("{ENV_PREFIX}{name}")).ok()
+}
+
+/// Value of `name` in the environment, or contents of the file its `_FILE` variant points to.
+fn secret_var(name: &str) -> Result<Option<String>, Error> {
+    if let Some(value) = var(name) {
+        return Ok(Some(value));
+    }
+    match var(&format!("{name}_FILE")) {
+        Some(path) => read_secret(Path::new(&path)).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn read_secret(path: &Path) -> Result<String, Error> {
+    read_to_string(path)
+        .map(|secret| secret.trim().to_string())
+        .map_err(|e| Error::Read(path.to_path_buf(), e))
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let data = read_to_string(path).map_err(|e| Error::Read(path.to_path_buf(), e))?;
+        let mut config: Config = serde_json::from_str(&data).map_err(|e| Error::Parse(path.to_path_buf(), e))?;
+        config.apply_env()?;
+        config.read_secrets()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<(), Error> {
+        if let Some(token) = secret_var("DISCORD_TOKEN")? {
+            self.discord.token = token;
+        }
+        if let Some(ids) = var("DISCORD_GUILDS_IDS") {
+            self.discord.guilds_ids = ids
+                .split(',')
+                .filter(|id| !id.trim().is_empty())
+                .map(|id| id.trim().parse())
+                .collect::<Result<_, _>>()
+                .map_err(|e| Error::Env(format!("{ENV_PREFIX}DISCORD_GUILDS_IDS"), format!("{e}")))?;
+        }
+
+        let client_id = var("SPOTIFY_CLIENT_ID");
+        let client_secret = secret_var("SPOTIFY_CLIENT_SECRET")?;
+        let market = var("SPOTIFY_MARKET");
+        if client_id.is_some() || client_secret.is_some() || market.is_some() {
+            let spotify = self.spotify.get_or_insert_with(|| SpotifyConfig {
+                client_id: String::new(),
+                client_secret: String::new(),
+                client_secret_file: None,
+                market: default_market(),
+            });
+            if let Some(client_id) = client_id {
+                spotify.client_id = client_id;
+            }
+            if let Some(client_secret) = client_secret {
+                spotify.client_secret = client_secret;
+                spotify.client_secret_file = None;
+            }
+            if let Some(market) = market {
+                spotify.market = market;
+            }
+        }
+
+        if let Some(shards) = var("DISCORD_SHARDS") {
+            self.discord.shards = match shards.trim() {
+                "auto" => None,
+                count => Some(
+                    count
+                        .parse()
+                        .map_err(|e| Error::Env(format!("{ENV_PREFIX}DISCORD_SHARDS"), format!("{e}")))?,
+                ),
+            };
+        }
+
+        if let Some(registration) = var("DISCORD_REGISTRATION") {
+            self.discord.registration = serde_json::from_value(Value::String(registration))
+                .map_err(|e| Error::Env(format!("{ENV_PREFIX}DISCORD_REGISTRATION"), format!("{e}")))?;
+        }
+
+        if let Some(database) = var("DATABASE") {
+            self.database = PathBuf::from(database);
+        }
+
+        if let Some(backends) = var("YOUTUBE_BACKENDS") {
+            self.youtube.backends = backends
+                .split(',')
+                .map(|backend| serde_json::from_value(Value::String(backend.trim().to_string())))
+                .collect::<Result<_, _>>()
+                .map_err(|e| Error::Env(format!("{ENV_PREFIX}YOUTUBE_BACKENDS"), format!("{e}")))?;
+        }
+        Ok(())
+    }
+
+    fn read_secrets(&mut self) -> Result<(), Error> {
+        if self.discord.token.is_empty() {
+            if let Some(path) = &self.discord.token_file {
+                self.discord.token = read_secret(path)?;
+            }
+        }
+        if let Some(spotify) = &mut self.spotify {
+            if let Some(path) = &spotify.client_secret_file {
+                if spotify.client_secret.is_empty() {
+                    spotify.client_secret = read_secret(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.discord.token.is_empty() {
+            return Err(Error::Invalid(format!(
+                "discord.token is empty, set it, discord.token_file or {ENV_PREFIX}DISCORD_TOKEN"
+            )));
+        }
+        if self.discord.shards == Some(0) {
+            return Err(Error::Invalid("discord.shards has to be above 0".to_string()));
+        }
+        if let Some(spotify) = &self.spotify {
+            if spotify.client_id.is_empty() != spotify.client_secret.is_empty() {
+                return Err(Error::Invalid(
+                    "spotify needs both client_id and client_secret".to_string(),
+                ));
+            }
+            if spotify.market.len() != 2 || !spotify.market.chars().all(|c| c.is_ascii_uppercase()) {
+                return Err(Error::Invalid(format!(
+                    "spotify.market {} is not a two letter country code",
+                    spotify.market
+                )));
+            }
+        }
+        if self.limits.playlist_songs == 0 || self.limits.queue == 0 {
+            return Err(Error::Invalid("limits have to be above 0".to_string()));
+        }
+        if self.youtube.backends.is_empty() {
+            return Err(Error::Invalid("youtube.backends is empty".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Config {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn fills_defaults() {
+        let config = parse(r#"{ "discord": { "token": "token" } }"#);
+        assert!(config.spotify.is_none());
+        assert_eq!(config.discord.registration, Registration::Guilds);
+        assert_eq!(config.discord.shards, None);
+        assert_eq!(config.youtube.backends, [Backend::Rustube, Backend::YtDlp]);
+        assert_eq!(config.limits.playlist_songs, 500);
+        assert_eq!(config.limits.queue, 1000);
+        assert_eq!(config.database, PathBuf::from("./jacabot.db"));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn reads_every_section() {
+        let config = parse(
+            r#"{
+                "discord": { "token": "token", "guilds_ids": [1, 2], "registration": "global", "shards": 2 },
+                "spotify": { "client_id": "id", "client_secret": "secret" },
+                "youtube": { "backends": ["yt-dlp"] },
+                "limits": { "queue": 10 },
+                "database": "/var/lib/jacabot.db"
+            }"#,
+        );
+        assert_eq!(config.discord.guilds_ids, [1, 2]);
+        assert_eq!(config.discord.registration, Registration::Global);
+        assert_eq!(config.discord.shards, Some(2));
+        assert_eq!(config.spotify.as_ref().map(|s| s.market.as_str()), Some("PL"));
+        assert_eq!(config.youtube.backends, [Backend::YtDlp]);
+        assert_eq!(config.limits.playlist_songs, 500);
+        assert_eq!(config.limits.queue, 10);
+        assert_eq!(config.database, PathBuf::from("/var/lib/jacabot.db"));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_values() {
+        assert!(serde_json::from_str::<Config>(r#"{ "discord": { "registration": "everywhere" } }"#).is_err());
+        assert!(serde_json::from_str::<Config>(r#"{ "discord": {}, "youtube": { "backends": ["vlc"] } }"#).is_err());
+        assert!(serde_json::from_str::<Config>(r#"{ "spotify": {} }"#).is_err());
+    }
+
+    #[test]
+    fn validates() {
+        let invalid = [
+            r#"{ "discord": {} }"#,
+            r#"{ "discord": { "token": "token", "shards": 0 } }"#,
+            r#"{ "discord": { "token": "token" }, "spotify": { "client_id": "id" } }"#,
+            r#"{
+                "discord": { "token": "token" },
+                "spotify": { "client_id": "id", "client_secret": "secret", "market": "pl" }
+            }"#,
+            r#"{ "discord": { "token": "token" }, "limits": { "queue": 0 } }"#,
+            r#"{ "discord": { "token": "token" }, "youtube": { "backends": [] } }"#,
+        ];
+        for json in invalid {
+            assert!(matches!(parse(json).validate(), Err(Error::Invalid(_))), "{json} passed validation");
         }
     }
-});
+
+    #[test]
+    fn reads_secret_files() {
+        let path = env::temp_dir().join(format!("jacabot-token-{}", std::process::id()));
+        std::fs::write(&path, "from file\n").unwrap();
+        let mut config = parse(&format!(r#"{{ "discord": {{ "token_file": {:?} }} }}"#, path));
+        config.read_secrets().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(config.discord.token, "from file");
+
+        let mut config = parse(&format!(r#"{{ "discord": {{ "token": "inline", "token_file": {:?} }} }}"#, path));
+        config.read_secrets().unwrap();
+        assert_eq!(config.discord.token, "inline");
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    panic,
+    path::Path,
+    sync::{Mutex, MutexGuard, OnceLock},
+};
+
+use log::{trace, warn};
+use rusqlite::Connection;
+
+/// Columns added after their table was created, existing databases get them on start.
+const MIGRATIONS: [&str; 2] = [
+    "ALTER TABLE settings ADD COLUMN language TEXT",
+    "ALTER TABLE settings ADD COLUMN autoplay INTEGER NOT NULL DEFAULT 0",
+];
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS plays (
+        id INTEGER PRIMARY KEY,
+        guild_id INTEGER NOT NULL,
+        user_id INTEGER NOT NULL,
+        url TEXT NOT NULL,
+        title TEXT NOT NULL,
+        session INTEGER NOT NULL,
+        played_at INTEGER NOT NULL,
+        skipped INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX IF NOT EXISTS plays_guild ON plays (guild_id, session);
+    CREATE TABLE IF NOT EXISTS likes (
+        guild_id INTEGER NOT NULL,
+        user_id INTEGER NOT NULL,
+        url TEXT NOT NULL,
+        PRIMARY KEY (guild_id, user_id, url)
+    );
+    CREATE TABLE IF NOT EXISTS settings (
+        guild_id INTEGER PRIMARY KEY,
+        volume INTEGER,
+        announce_channel INTEGER,
+        dj_role INTEGER,
+        playlist_songs INTEGER,
+        queue INTEGER,
+        language TEXT,
+        autoplay INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS sessions (
+        guild_id INTEGER PRIMARY KEY,
+        channel_id INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        looping INTEGER NOT NULL,
+        volume REAL NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS session_tracks (
+        guild_id INTEGER NOT NULL,
+        ordinal INTEGER NOT NULL,
+        url TEXT NOT NULL,
+        title TEXT,
+        requester INTEGER,
+        announce_channel INTEGER NOT NULL,
+        PRIMARY KEY (guild_id, ordinal)
+    );
+    CREATE TABLE IF NOT EXISTS registrations (
+        scope INTEGER PRIMARY KEY,
+        hash TEXT NOT NULL
+    );
+";
+
+static DB: OnceLock<Mutex<Connection>> = OnceLock::new();
+
+/// Opens the database at `path` and brings its schema up to date, before anything queries it.
+pub fn open(path: &Path) -> rusqlite::Result<()> {
+    let connection = Connection::open(path)?;
+    connection.execute_batch(SCHEMA)?;
+    for migration in MIGRATIONS {
+        // Fails with duplicate column once applied
+        if let Err(e) = connection.execute(migration, []) {
+            trace!("Skipping migration `{migration}`: {e}");
+        }
+    }
+    if DB.set(Mutex::new(connection)).is_err() {
+        warn!("Database is already open, keeping the first connection");
+    }
+    Ok(())
+}
+
+pub fn connection() -> MutexGuard<'static, Connection> {
+    DB.get().expect("Database used before open").lock().unwrap()
+}
+
+/// Runs `query` on a blocking thread, SQLite calls would hold up the async ones.
+pub async fn run<T, F>(query: F) -> T
+where
+    F: FnOnce(&mut Connection) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(move || query(&mut connection()))
+        .await
+        .unwrap_or_else(|e| panic::resume_unwind(e.into_panic()))
+}
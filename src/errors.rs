@@ -1,4 +1,138 @@
-enum _Error {
+use std::{fmt, time::Duration};
+
+use log::{error, trace, warn};
+use serenity::{
+    model::prelude::interaction::{application_command::ApplicationCommandInteraction, InteractionResponseType},
+    prelude::Context,
+};
+use songbird::{error::JoinError, tracks::TrackError};
+
+use crate::{commands::play::resolver, locale::Lang};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    NotInGuild,
     NotInVoice,
+    BotNotInVoice,
+    OtherChannel,
+    NotDj,
+    NothingPlaying,
+    QueueFull,
+    MissingPermissions,
+    Cooldown(Duration),
+    UnknownCommand(String),
+    Join(JoinError),
+    Track(TrackError),
+    Resolve(resolver::Error),
+    Discord(serenity::Error),
+    Database(rusqlite::Error),
+    Config(String),
+}
+
+impl Error {
+    /// Errors caused by the bot itself rather than by how the command was used.
+    fn internal(&self) -> bool {
+        matches!(
+            self,
+            Error::Join(_) | Error::Track(_) | Error::Discord(_) | Error::Database(_) | Error::Config(_) | Error::UnknownCommand(_)
+        )
+    }
+}
+
+impl Error {
+    /// What went wrong, in words the user understands.
+    pub fn message(&self, lang: Lang) -> String {
+        let key = match self {
+            Error::NotInGuild => "error.not_in_guild",
+            Error::NotInVoice => "error.not_in_voice",
+            Error::BotNotInVoice => "error.bot_not_in_voice",
+            Error::OtherChannel => "error.other_channel",
+            Error::NotDj => "error.not_dj",
+            Error::NothingPlaying => "error.nothing_playing",
+            Error::QueueFull => "error.queue_full",
+            Error::MissingPermissions => "error.missing_permissions",
+            Error::Cooldown(left) => {
+                return lang.tr_with("error.cooldown", &[("seconds", &(left.as_secs() + 1))]);
+            }
+            Error::UnknownCommand(_) => "error.unknown_command",
+            Error::Join(_) => "error.join",
+            Error::Track(_) => "error.track",
+            Error::Resolve(e) => e.key(),
+            Error::Discord(_) => "error.discord",
+            Error::Database(_) => "error.database",
+            Error::Config(_) => "error.config",
+        };
+        lang.tr(key)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message(Lang::En))
+    }
+}
+
+impl From<JoinError> for Error {
+    fn from(e: JoinError) -> Self {
+        Error::Join(e)
+    }
+}
+
+impl From<TrackError> for Error {
+    fn from(e: TrackError) -> Self {
+        Error::Track(e)
+    }
+}
+
+impl From<resolver::Error> for Error {
+    fn from(e: resolver::Error) -> Self {
+        Error::Resolve(e)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Database(e)
+    }
+}
+
+impl From<serenity::Error> for Error {
+    fn from(e: serenity::Error) -> Self {
+        Error::Discord(e)
+    }
+}
+
+/// Logs `error` and tells the user about it in a message only they can see.
+pub async fn report(ctx: &Context, interaction: &ApplicationCommandInteraction, error: Error) {
+    let command = &interaction.data.name;
+    if error.internal() {
+        error!("/{command} failed: {error:?}");
+    } else {
+        trace!("/{command} refused: {error:?}");
+    }
+
+    let content = error.message(Lang::of(interaction));
+    let responded = interaction
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|message| message.content(&content).ephemeral(true))
+        })
+        .await;
+    if responded.is_ok() {
+        return;
+    }
 
+    // Already answered with something like "Processing please wait", which would be left hanging
+    if let Err(e) = interaction.delete_original_interaction_response(&ctx.http).await {
+        warn!("{e}");
+    }
+    if let Err(e) = interaction
+        .create_followup_message(&ctx.http, |message| message.content(&content).ephemeral(true))
+        .await
+    {
+        warn!("Cannot report error of /{command}: {e}");
+    }
 }
@@ -0,0 +1,223 @@
+use std::{collections::HashMap, fmt, sync::LazyLock};
+
+use log::error;
+use serenity::{
+    builder::{CreateApplicationCommand, CreateApplicationCommandOption},
+    model::prelude::{interaction::application_command::ApplicationCommandInteraction, GuildId},
+};
+
+use crate::settings;
+
+type Catalog = HashMap<String, String>;
+
+fn parse(lang: Lang, source: &str) -> Catalog {
+    serde_json::from_str(source).unwrap_or_else(|e| {
+        error!("Cannot parse {} translations: {e}", lang.code());
+        Catalog::new()
+    })
+}
+
+static EN: LazyLock<Catalog> = LazyLock::new(|| parse(Lang::En, include_str!("../locales/en.json")));
+static PL: LazyLock<Catalog> = LazyLock::new(|| parse(Lang::Pl, include_str!("../locales/pl.json")));
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    En,
+    Pl,
+}
+
+impl Lang {
+    pub const ALL: [Lang; 2] = [Lang::En, Lang::Pl];
+
+    /// Language of replies to `interaction`, the guild setting wins over the user's client language.
+    pub fn of(interaction: &ApplicationCommandInteraction) -> Lang {
+        interaction
+            .guild_id
+            .and_then(|guild_id| settings::get(guild_id).language)
+            .unwrap_or_else(|| Lang::from_locale(&interaction.locale))
+    }
+
+    /// Language of messages not sent in reply to anyone, like autoplay announcements.
+    pub fn for_guild(guild_id: GuildId) -> Lang {
+        settings::get(guild_id).language.unwrap_or_default()
+    }
+
+    /// Discord locales are like `en-US` or `pl`.
+    pub fn from_locale(locale: &str) -> Lang {
+        if locale.starts_with("pl") {
+            Lang::Pl
+        } else {
+            Lang::En
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Lang> {
+        Lang::ALL.into_iter().find(|lang| lang.code() == code)
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::Pl => "pl",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lang::En => "English",
+            Lang::Pl => "Polski",
+        }
+    }
+
+    fn catalog(self) -> &'static Catalog {
+        match self {
+            Lang::En => &EN,
+            Lang::Pl => &PL,
+        }
+    }
+
+    fn lookup(self, key: &str) -> Option<&'static str> {
+        self.catalog().get(key).map(String::as_str)
+    }
+
+    /// Missing translations fall back to English, and then to the key itself so they are easy to spot.
+    pub fn tr(self, key: &str) -> String {
+        self.lookup(key)
+            .or_else(|| Lang::En.lookup(key))
+            .unwrap_or(key)
+            .to_string()
+    }
+
+    /// Like [`Lang::tr`], replacing `{name}` placeholders with `args`.
+    ///
+    /// Goes over the template once, so braces in the values, like in song titles, stay as they are.
+    pub fn tr_with(self, key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        let template = self.tr(key);
+        let mut text = String::with_capacity(template.len());
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let arg = after.find('}').and_then(|end| {
+                args.iter()
+                    .find(|(name, _)| *name == &after[..end])
+                    .map(|(_, value)| (end, value))
+            });
+            match arg {
+                Some((end, value)) => {
+                    text.push_str(&value.to_string());
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    text.push('{');
+                    rest = after;
+                }
+            }
+        }
+        text.push_str(rest);
+        text
+    }
+}
+
+/// Discord locales the translations are registered under, English is the default one.
+const DISCORD_LOCALES: [(Lang, &str); 1] = [(Lang::Pl, "pl")];
+
+/// Sets names and descriptions of commands and options in every language.
+pub trait Localize {
+    fn localized_description(&mut self, key: &str) -> &mut Self;
+
+    /// Translated names are looked up under `{key}.name`, Discord still sends the English one back.
+    fn localized_name(&mut self, name: &str, key: &str) -> &mut Self;
+}
+
+impl Localize for CreateApplicationCommand {
+    fn localized_description(&mut self, key: &str) -> &mut Self {
+        self.description(Lang::En.tr(key));
+        for (lang, locale) in DISCORD_LOCALES {
+            if let Some(translated) = lang.lookup(key) {
+                self.description_localized(locale, translated);
+            }
+        }
+        self
+    }
+
+    fn localized_name(&mut self, name: &str, key: &str) -> &mut Self {
+        self.name(name);
+        for (lang, locale) in DISCORD_LOCALES {
+            if let Some(translated) = lang.lookup(&format!("{key}.name")) {
+                self.name_localized(locale, translated);
+            }
+        }
+        self
+    }
+}
+
+impl Localize for CreateApplicationCommandOption {
+    fn localized_description(&mut self, key: &str) -> &mut Self {
+        self.description(Lang::En.tr(key));
+        for (lang, locale) in DISCORD_LOCALES {
+            if let Some(translated) = lang.lookup(key) {
+                self.description_localized(locale, translated);
+            }
+        }
+        self
+    }
+
+    fn localized_name(&mut self, name: &str, key: &str) -> &mut Self {
+        self.name(name);
+        for (lang, locale) in DISCORD_LOCALES {
+            if let Some(translated) = lang.lookup(&format!("{key}.name")) {
+                self.name_localized(locale, translated);
+            }
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_placeholders() {
+        let text = Lang::En.tr_with("play.over_limit", &[("over", &3), ("limit", &500)]);
+        assert_eq!(text, "3 over the limit of 500");
+        let text = Lang::Pl.tr_with("play.skipped", &[("count", &2), ("reasons", &"pliki lokalne: 2")]);
+        assert_eq!(text, "Pominięto 2: pliki lokalne: 2");
+    }
+
+    #[test]
+    fn keeps_braces_of_values_and_unknown_placeholders() {
+        let text = Lang::En.tr_with("search.results", &[("query", &"{query} {count}")]);
+        assert_eq!(text, "Results for `{query} {count}`");
+        let text = Lang::En.tr_with("play.over_limit", &[("over", &"{")]);
+        assert_eq!(text, "{ over the limit of {limit}");
+    }
+
+    #[test]
+    fn falls_back_to_english_and_key() {
+        assert_eq!(Lang::Pl.tr("no.such.key"), "no.such.key");
+        assert_eq!(Lang::Pl.tr_with("no.{such}.key", &[("such", &1)]), "no.1.key");
+    }
+
+    #[test]
+    fn locales_have_same_keys() {
+        let mut missing: Vec<&String> = EN.keys().filter(|key| !PL.contains_key(*key)).collect();
+        missing.extend(PL.keys().filter(|key| !EN.contains_key(*key)));
+        // Command names can stay English
+        missing.retain(|key| !key.ends_with(".name"));
+        assert!(missing.is_empty(), "not translated: {missing:?}");
+    }
+
+    #[test]
+    fn names_are_valid_for_discord() {
+        for (key, name) in PL.iter().filter(|(key, _)| key.ends_with(".name")) {
+            assert!(
+                (1..=32).contains(&name.chars().count())
+                    && name.chars().all(|c| c == '-' || c == '_' || c.is_lowercase() || c.is_numeric()),
+                "{key} is not a valid name: {name}"
+            );
+        }
+    }
+}
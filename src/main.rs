@@ -1,41 +1,54 @@
 #![feature(once_cell)]
 use log::{error, info, warn};
 use std::{
+    future::pending,
+    path::PathBuf,
+    process::exit,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::Duration,
-    process::exit
 };
 
 use serenity::{
     async_trait,
+    client::bridge::gateway::{event::ShardStageUpdateEvent, ShardManager},
     model::{
         application::interaction::Interaction,
         gateway::Ready,
-        prelude::{GuildId, Message, RoleId, ReactionType, EmojiId}},
-    prelude::{Client, Context, EventHandler, GatewayIntents},
+        prelude::{Guild, Message, RoleId, ReactionType, EmojiId, UnavailableGuild}},
+    prelude::{Client, Context, EventHandler, GatewayIntents, Mutex},
 };
 
-use songbird::SerenityInit;
+use songbird::{SerenityInit, SongbirdKey};
+use tokio::signal::unix::{signal, SignalKind};
+
+use commands::{play::session, registration};
 
 mod config;
+mod db;
 mod errors;
+mod locale;
+mod settings;
 mod commands;
 
-struct Handler;
+const SHARD_STATUS_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+struct Handler {
+    config_path: PathBuf,
+    started: AtomicBool,
+}
 
 #[async_trait]
 impl EventHandler for Handler {
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        if let Interaction::ApplicationCommand(command) = interaction {
-            match command.data.name.as_str() {
-                "ping" => commands::ping::run(&command, &ctx).await,
-                "play" => commands::play::run(&command, &ctx).await,
-                "skip" => commands::skip::run(&command, &ctx).await,
-                "stop" => commands::stop::run(&command, &ctx).await,
-                "pause" => commands::pause::run(&command, &ctx).await,
-                "resume" => commands::resume::run(&command, &ctx).await,
-                "repeat" => commands::repeat::run(&command, &ctx).await,
-                _ => {}
-            };
+        if let Interaction::Autocomplete(autocomplete) = interaction {
+            commands::COMMANDS.autocomplete(&autocomplete, &ctx).await;
+        } else if let Interaction::ApplicationCommand(command) = interaction {
+            if let Err(e) = commands::COMMANDS.run(&command, &ctx).await {
+                errors::report(&ctx, &command, e).await;
+            }
         }
     }
     async fn message(&self, ctx: Context, msg: Message) {
@@ -47,53 +60,118 @@ impl EventHandler for Handler {
     }
     async fn ready(&self, ctx: Context, ready: Ready) {
         tokio::time::sleep(Duration::from_secs(2)).await;
-        info!("{} is connected!", ready.user.name);
+        let shard = ready.shard.unwrap_or([0, 1]);
+        info!("{} is connected on shard {}/{}!", ready.user.name, shard[0] + 1, shard[1]);
+
+        // Guild commands are handled as guilds come in, global ones are shared by every shard
+        if shard[0] == 0 {
+            registration::global(&ctx.http).await;
+        }
+        // Each shard rejoins voice channels of its own guilds
+        tokio::spawn(session::restore(ctx.clone(), shard));
+
+        // Ready comes again after reconnecting and once for every shard, all of this should happen once
+        if !self.started.swap(true, Ordering::SeqCst) {
+            tokio::spawn(config::watch(self.config_path.clone()));
+            tokio::spawn(apply_reloads(ctx.clone()));
+        }
+    }
+    async fn shard_stage_update(&self, _ctx: Context, event: ShardStageUpdateEvent) {
+        info!("Shard {} is {} (was {})", event.shard_id.0, event.new, event.old);
+    }
+    async fn guild_create(&self, ctx: Context, guild: Guild, _is_new: bool) {
+        registration::guild(&ctx.http, guild.id).await;
+    }
+    async fn guild_delete(&self, _ctx: Context, incomplete: UnavailableGuild, _full: Option<Guild>) {
+        // Unavailable guilds are only down for a while, the bot is still in them
+        if !incomplete.unavailable {
+            registration::forget(incomplete.id).await;
+        }
+    }
+}
+
+/// Applies everything that can change without restart whenever config gets reloaded.
+async fn apply_reloads(ctx: Context) {
+    let mut configs = config::subscribe();
+    let mut current = config::get();
+    while configs.changed().await.is_ok() {
+        let new = configs.borrow().clone();
+
+        if new.spotify != current.spotify {
+            commands::play::spotify::configure(new.spotify.as_ref());
+            info!("Spotify credentials updated");
+        }
+        if new.youtube.backends != current.youtube.backends {
+            commands::play::resolver::REGISTRY.configure_youtube(&new.youtube.backends);
+            info!("YouTube backends updated");
+        }
+        if new.discord.guilds_ids != current.discord.guilds_ids
+            || new.discord.registration != current.discord.registration
+        {
+            info!("Command registration changed, updating guilds");
+            registration::sync(&ctx).await;
+        }
+        // Limits are read on every use, nothing to do for them
+        current = new;
+    }
+}
 
-        let guilds_ids = match config::DISCORD_CONFIG.get("guilds_ids").and_then(|g| g.as_array()) {
-            Some(g) => g,
-            None => {
-                warn!("Cannot get guilds_ids. Invaild config?");
-                return;
+/// Logs stage and latency of every shard now and then.
+async fn log_shards(shard_manager: Arc<Mutex<ShardManager>>) {
+    let mut interval = tokio::time::interval(SHARD_STATUS_INTERVAL);
+    loop {
+        interval.tick().await;
+        let shard_manager = shard_manager.lock().await;
+        let runners = shard_manager.runners.lock().await;
+        for (id, runner) in runners.iter() {
+            match runner.latency {
+                Some(latency) => info!("Shard {} is {}, latency {}ms", id.0, runner.stage, latency.as_millis()),
+                None => info!("Shard {} is {}, latency unknown", id.0, runner.stage),
             }
-        };
-        info!("Adding {} guilds", guilds_ids.len());
-        for guild_id in guilds_ids {
-            let guild_id = GuildId(match guild_id.as_u64() {
-                Some(id) => id,
-                None => {
-                    warn!("Cannot get GuildId. Not a number?");
-                    continue
-                }
-            });
-
-            if let Err(e) = GuildId::set_application_commands(&guild_id, &ctx.http, |commands| {
-                commands
-                    .create_application_command(|command| commands::ping::register(command))
-                    .create_application_command(|command| commands::play::register(command))
-                    .create_application_command(|command| commands::skip::register(command))
-                    .create_application_command(|command| commands::stop::register(command))
-                    .create_application_command(|command| commands::pause::register(command))
-                    .create_application_command(|command| commands::resume::register(command))
-                    .create_application_command(|command| commands::repeat::register(command))
-            })
-            .await
-            {
-                warn!("{e}");
-            };
         }
     }
 }
 
+/// Waits for SIGTERM or Ctrl+C.
+async fn shutdown_signal() {
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => Some(terminate),
+        Err(e) => {
+            warn!("Cannot listen for SIGTERM, only Ctrl+C saves sessions: {e}");
+            None
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = async {
+            match &mut terminate {
+                Some(terminate) => terminate.recv().await,
+                None => pending().await,
+            }
+        } => {}
+    }
+}
+
 #[tokio::main]
 async fn main() {
     env_logger::init();
-    let token = match config::DISCORD_CONFIG.get("token").and_then(|t| t.as_str()) {
-        Some(t) => t,
-        None => {
-            error!("Cannot get token. No token in config file?");
+    let config_path = config::path();
+    let config = match config::init(&config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{e}");
             exit(1)
         }
     };
+    if let Err(e) = db::open(&config.database) {
+        error!("Cannot open database {}: {e}", config.database.display());
+        exit(1)
+    }
+    if let Err(e) = settings::load() {
+        error!("Cannot load settings: {e}");
+        exit(1)
+    }
+    let token = &config.discord.token;
 
     let intents = GatewayIntents::GUILDS
         | GatewayIntents::GUILD_VOICE_STATES
@@ -102,7 +180,10 @@ async fn main() {
         | GatewayIntents::MESSAGE_CONTENT;
 
     let mut client = match Client::builder(token, intents)
-        .event_handler(Handler)
+        .event_handler(Handler {
+            config_path,
+            started: AtomicBool::new(false),
+        })
         .register_songbird()
         .await
     {
@@ -113,7 +194,31 @@ async fn main() {
         }
     };
 
-    if let Err(why) = client.start().await {
+    let shard_manager = client.shard_manager.clone();
+    let cache = client.cache_and_http.cache.clone();
+    let data = client.data.clone();
+    tokio::spawn(log_shards(client.shard_manager.clone()));
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutting down");
+        let manager = data.read().await.get::<SongbirdKey>().cloned();
+        if let Some(manager) = manager {
+            session::save(&manager, cache.guilds()).await;
+        }
+        shard_manager.lock().await.shutdown_all().await;
+    });
+
+    let started = match config.discord.shards {
+        Some(shards) => {
+            info!("Starting {shards} shards");
+            client.start_shards(shards).await
+        }
+        None => {
+            info!("Starting as many shards as Discord recommends");
+            client.start_autosharded().await
+        }
+    };
+    if let Err(why) = started {
         error!("Client error: {why}");
     }
 }
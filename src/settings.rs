@@ -0,0 +1,102 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use rusqlite::params;
+use serenity::model::prelude::{ChannelId, GuildId, RoleId};
+
+use crate::{config, db, locale::Lang};
+
+pub const MAX_VOLUME: u8 = 200;
+
+/// Settings are read on nearly every command and track, so they are all kept in memory.
+static CACHE: LazyLock<Mutex<HashMap<GuildId, Settings>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Preferences of a guild, `None` means the default.
+#[derive(Default, Clone)]
+pub struct Settings {
+    /// Percent of the original volume.
+    pub volume: Option<u8>,
+    /// Channel now playing messages go to instead of the one the song was requested in.
+    pub announce_channel: Option<ChannelId>,
+    pub dj_role: Option<RoleId>,
+    pub playlist_songs: Option<usize>,
+    pub queue: Option<usize>,
+    /// Language of replies, overriding the one of each user.
+    pub language: Option<Lang>,
+    /// Whether related songs are queued when the queue runs out.
+    pub autoplay: bool,
+}
+
+impl Settings {
+    pub fn volume(&self) -> f32 {
+        self.volume.unwrap_or(100) as f32 / 100.0
+    }
+
+    /// Guild limits can only be stricter than the global ones.
+    pub fn playlist_songs(&self) -> usize {
+        let limit = config::get().limits.playlist_songs;
+        self.playlist_songs.map_or(limit, |songs| songs.min(limit))
+    }
+
+    pub fn queue(&self) -> usize {
+        let limit = config::get().limits.queue;
+        self.queue.map_or(limit, |queue| queue.min(limit))
+    }
+}
+
+/// Reads settings of every guild into memory, so [`get`] never waits for the database.
+pub fn load() -> rusqlite::Result<()> {
+    let db = db::connection();
+    let mut statement = db.prepare(
+        "SELECT guild_id, volume, announce_channel, dj_role, playlist_songs, queue, language, autoplay FROM settings",
+    )?;
+    let settings = statement
+        .query_map([], |row| {
+            Ok((
+                GuildId(row.get(0)?),
+                Settings {
+                    volume: row.get(1)?,
+                    announce_channel: row.get::<_, Option<u64>>(2)?.map(ChannelId),
+                    dj_role: row.get::<_, Option<u64>>(3)?.map(RoleId),
+                    playlist_songs: row.get(4)?,
+                    queue: row.get(5)?,
+                    language: row.get::<_, Option<String>>(6)?.as_deref().and_then(Lang::from_code),
+                    autoplay: row.get(7)?,
+                },
+            ))
+        })?
+        .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+    *CACHE.lock().unwrap() = settings;
+    Ok(())
+}
+
+/// Guilds missing from the loaded settings never changed any.
+pub fn get(guild_id: GuildId) -> Settings {
+    CACHE.lock().unwrap().get(&guild_id).cloned().unwrap_or_default()
+}
+
+pub async fn set(guild_id: GuildId, settings: Settings) -> rusqlite::Result<()> {
+    let stored = settings.clone();
+    db::run(move |db| {
+        db.execute(
+            "INSERT OR REPLACE INTO settings
+             (guild_id, volume, announce_channel, dj_role, playlist_songs, queue, language, autoplay)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                guild_id.0,
+                stored.volume,
+                stored.announce_channel.map(|c| c.0),
+                stored.dj_role.map(|r| r.0),
+                stored.playlist_songs,
+                stored.queue,
+                stored.language.map(Lang::code),
+                stored.autoplay,
+            ],
+        )
+    })
+    .await?;
+    CACHE.lock().unwrap().insert(guild_id, settings);
+    Ok(())
+}